@@ -0,0 +1,419 @@
+use crate::*;
+use near_contract_standards::non_fungible_token::approval::{
+    ext_approval_receiver, NonFungibleTokenApproval,
+};
+use near_contract_standards::non_fungible_token::refund_deposit;
+use near_sdk::json_types::U64;
+use near_sdk::{assert_one_yocto, require, Balance, Gas};
+
+const GAS_FOR_NFT_APPROVE: Gas = Gas(10_000_000_000_000);
+const NO_DEPOSIT: Balance = 0;
+
+fn assert_at_least_one_yocto() {
+    require!(
+        env::attached_deposit() >= 1,
+        "Requires attached deposit of at least 1 yoctoNEAR"
+    )
+}
+
+fn refund_storage(account_id: AccountId, storage_released: u64) {
+    if storage_released > 0 {
+        Promise::new(account_id)
+            .transfer(Balance::from(storage_released) * env::storage_byte_cost());
+    }
+}
+
+impl Contract {
+    /// Whether the approval `approval_id` of `token_id` had an expiration which has passed.
+    pub(crate) fn is_approval_expired(&self, token_id: &TokenId, approval_id: u64) -> bool {
+        self.approval_expirations
+            .get(token_id)
+            .and_then(|expirations| expirations.get(&approval_id).copied())
+            .map_or(false, |expires_at| expires_at <= env::block_timestamp())
+    }
+
+    /// Whether `operator_id` holds an unexpired approval for all tokens of `owner_id`.
+    pub(crate) fn is_operator_for(&self, owner_id: &AccountId, operator_id: &AccountId) -> bool {
+        self.operator_approvals
+            .get(owner_id)
+            .and_then(|operators| operators.get(operator_id).copied())
+            .map_or(false, |expires_at| {
+                expires_at.map_or(true, |expires_at| expires_at > env::block_timestamp())
+            })
+    }
+
+    /// Keep only the expirations of `token_id` whose approval id matches `keep`.
+    pub(crate) fn internal_retain_approval_expirations<F>(&mut self, token_id: &TokenId, keep: F)
+    where
+        F: Fn(&u64) -> bool,
+    {
+        if let Some(mut expirations) = self.approval_expirations.get(token_id) {
+            expirations.retain(|approval_id, _| keep(approval_id));
+            if expirations.is_empty() {
+                self.approval_expirations.remove(token_id);
+            } else {
+                self.approval_expirations.insert(token_id, &expirations);
+            }
+        }
+    }
+
+    fn internal_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        expires_at: Option<u64>,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        let initial_storage_usage = env::storage_usage();
+        assert_at_least_one_yocto();
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+        require!(
+            env::predecessor_account_id() == owner_id,
+            "Predecessor must be token owner."
+        );
+        self.assert_not_blocked(&owner_id);
+        self.assert_not_blocked(&account_id);
+        self.assert_not_staked(&token_id);
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at > env::block_timestamp(),
+                "Expiration must be in the future"
+            );
+        }
+
+        let approvals_by_id = self.tokens.approvals_by_id.as_mut().unwrap();
+        let next_approval_id_by_id = self.tokens.next_approval_id_by_id.as_mut().unwrap();
+        let mut approved_account_ids = approvals_by_id.get(&token_id).unwrap_or_default();
+        let approval_id: u64 = next_approval_id_by_id.get(&token_id).unwrap_or(1u64);
+        let old_approval_id = approved_account_ids.insert(account_id.clone(), approval_id);
+        approvals_by_id.insert(&token_id, &approved_account_ids);
+        next_approval_id_by_id.insert(&token_id, &(approval_id + 1));
+
+        // the replaced approval id can never be used again, so neither can its expiration
+        let mut expirations = self.approval_expirations.get(&token_id).unwrap_or_default();
+        if let Some(old_approval_id) = old_approval_id {
+            expirations.remove(&old_approval_id);
+        }
+        if let Some(expires_at) = expires_at {
+            expirations.insert(approval_id, expires_at);
+        }
+        if expirations.is_empty() {
+            self.approval_expirations.remove(&token_id);
+        } else {
+            self.approval_expirations.insert(&token_id, &expirations);
+        }
+
+        // a replaced approval can free storage, for instance when it dropped an expiration
+        let storage_usage = env::storage_usage();
+        refund_deposit(storage_usage.saturating_sub(initial_storage_usage));
+        refund_storage(
+            owner_id.clone(),
+            initial_storage_usage.saturating_sub(storage_usage),
+        );
+
+        msg.map(|msg| {
+            ext_approval_receiver::nft_on_approve(
+                token_id,
+                owner_id,
+                approval_id,
+                msg,
+                account_id,
+                NO_DEPOSIT,
+                env::prepaid_gas() - GAS_FOR_NFT_APPROVE,
+            )
+        })
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Same as `nft_approve`, but the approval stops being valid at the `expires_at` block
+    /// timestamp (in nanoseconds).
+    #[payable]
+    pub fn nft_approve_with_expiry(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        expires_at: U64,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        self.internal_approve(token_id, account_id, Some(expires_at.0), msg)
+    }
+
+    /// Approve `operator_id` to transfer every token the predecessor holds now or in the
+    /// future, optionally until the `expires_at` block timestamp (in nanoseconds).
+    #[payable]
+    pub fn nft_approve_all(&mut self, operator_id: AccountId, expires_at: Option<U64>) {
+        let initial_storage_usage = env::storage_usage();
+        assert_at_least_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        require!(
+            owner_id != operator_id,
+            "Cannot approve yourself as an operator"
+        );
+        self.assert_not_blocked(&owner_id);
+        self.assert_not_blocked(&operator_id);
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at.0 > env::block_timestamp(),
+                "Expiration must be in the future"
+            );
+        }
+
+        let mut operators = self.operator_approvals.get(&owner_id).unwrap_or_default();
+        operators.insert(operator_id, expires_at.map(|expires_at| expires_at.0));
+        self.operator_approvals.insert(&owner_id, &operators);
+
+        let storage_usage = env::storage_usage();
+        refund_deposit(storage_usage.saturating_sub(initial_storage_usage));
+        refund_storage(
+            owner_id,
+            initial_storage_usage.saturating_sub(storage_usage),
+        );
+    }
+
+    /// Revoke the approval of `operator_id` over all tokens of the predecessor.
+    #[payable]
+    pub fn nft_revoke_all_operator(&mut self, operator_id: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+
+        if let Some(mut operators) = self.operator_approvals.get(&owner_id) {
+            if operators.remove(&operator_id).is_some() {
+                if operators.is_empty() {
+                    self.operator_approvals.remove(&owner_id);
+                } else {
+                    self.operator_approvals.insert(&owner_id, &operators);
+                }
+            }
+        }
+        refund_storage(
+            owner_id,
+            initial_storage_usage.saturating_sub(env::storage_usage()),
+        );
+    }
+
+    pub fn nft_is_approved_for_all(&self, owner_id: AccountId, operator_id: AccountId) -> bool {
+        self.is_operator_for(&owner_id, &operator_id)
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenApproval for Contract {
+    #[payable]
+    fn nft_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        self.internal_approve(token_id, account_id, None, msg)
+    }
+
+    #[payable]
+    fn nft_revoke(&mut self, token_id: TokenId, account_id: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        assert_one_yocto();
+        let predecessor_account_id = env::predecessor_account_id();
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+        require!(
+            predecessor_account_id == owner_id,
+            "Predecessor must be token owner."
+        );
+
+        let approvals_by_id = self.tokens.approvals_by_id.as_mut().unwrap();
+        // if token has no approvals, or account_id was already not approved, do nothing
+        if let Some(mut approved_account_ids) = approvals_by_id.get(&token_id) {
+            if let Some(approval_id) = approved_account_ids.remove(&account_id) {
+                if approved_account_ids.is_empty() {
+                    approvals_by_id.remove(&token_id);
+                } else {
+                    approvals_by_id.insert(&token_id, &approved_account_ids);
+                }
+                self.internal_retain_approval_expirations(&token_id, |id| *id != approval_id);
+            }
+        }
+        refund_storage(
+            predecessor_account_id,
+            initial_storage_usage.saturating_sub(env::storage_usage()),
+        );
+    }
+
+    #[payable]
+    fn nft_revoke_all(&mut self, token_id: TokenId) {
+        let initial_storage_usage = env::storage_usage();
+        assert_one_yocto();
+        let predecessor_account_id = env::predecessor_account_id();
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+        require!(
+            predecessor_account_id == owner_id,
+            "Predecessor must be token owner."
+        );
+
+        let approvals_by_id = self.tokens.approvals_by_id.as_mut().unwrap();
+        if approvals_by_id.remove(&token_id).is_some() {
+            self.approval_expirations.remove(&token_id);
+        }
+        // refund owner for storage costs of all approvals and their expirations
+        refund_storage(
+            predecessor_account_id,
+            initial_storage_usage.saturating_sub(env::storage_usage()),
+        );
+    }
+
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: AccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+
+        let actual_approval_id = self
+            .tokens
+            .approvals_by_id
+            .as_ref()
+            .and_then(|by_id| by_id.get(&token_id))
+            .and_then(|approved_account_ids| {
+                approved_account_ids.get(&approved_account_id).copied()
+            });
+
+        match actual_approval_id {
+            Some(actual_approval_id) => {
+                approval_id.map_or(true, |approval_id| approval_id == actual_approval_id)
+                    && !self.is_approval_expired(&token_id, actual_approval_id)
+            }
+            // operators are approved for every token, but hold no approval id
+            None => approval_id.is_none() && self.is_operator_for(&owner_id, &approved_account_id),
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_STORAGE_COST};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApproval;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const APPROVAL_STORAGE_COST: u128 = 2_000_000_000_000_000_000_000;
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.nft_mint("0".to_string(), accounts(0), sample_token_metadata());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_approval_expires() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(APPROVAL_STORAGE_COST)
+            .block_timestamp(100)
+            .build());
+        contract.nft_approve_with_expiry("0".to_string(), accounts(1), U64(200), None);
+        assert!(contract.nft_is_approved("0".to_string(), accounts(1), Some(1)));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(200)
+            .build());
+        assert!(!contract.nft_is_approved("0".to_string(), accounts(1), Some(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Approval expired")]
+    fn test_transfer_with_expired_approval() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(APPROVAL_STORAGE_COST)
+            .block_timestamp(100)
+            .build());
+        contract.nft_approve_with_expiry("0".to_string(), accounts(1), U64(200), None);
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(300)
+            .build());
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+    }
+
+    #[test]
+    fn test_operator_transfer() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(APPROVAL_STORAGE_COST)
+            .build());
+        contract.nft_approve_all(accounts(1), None);
+        assert!(contract.nft_is_approved_for_all(accounts(0), accounts(1)));
+        assert!(contract.nft_is_approved("0".to_string(), accounts(1), None));
+        assert!(!contract.nft_is_approved("0".to_string(), accounts(1), Some(1)));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Sender not approved")]
+    fn test_revoked_operator_cannot_transfer() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(APPROVAL_STORAGE_COST)
+            .build());
+        contract.nft_approve_all(accounts(1), None);
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .build());
+        contract.nft_revoke_all_operator(accounts(1));
+        assert!(!contract.nft_is_approved_for_all(accounts(0), accounts(1)));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+    }
+}
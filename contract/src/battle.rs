@@ -0,0 +1,441 @@
+use crate::utils::{random_u64, refund_deposit_with_price};
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, Balance};
+
+const XP_PER_VICTORY: u64 = 50;
+const MAX_BATTLE_HISTORY: usize = 20;
+/// Time a warrior rests after a battle before it can fight again, in nanoseconds.
+const BATTLE_COOLDOWN: u64 = 60 * 60 * 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BattleRecord {
+    pub attacker_token_id: TokenId,
+    pub defender_token_id: TokenId,
+    pub winner_token_id: TokenId,
+    pub wager: U128,
+    pub timestamp: U64,
+}
+
+/// A battle offered by the owner of `attacker_token_id`, who escrowed `wager`. It is fought
+/// once the owner of `defender_token_id` accepts it by matching the wager.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Challenge {
+    pub challenger_id: AccountId,
+    pub attacker_token_id: TokenId,
+    pub defender_token_id: TokenId,
+    pub wager: U128,
+}
+
+impl Contract {
+    fn expect_token_owner(&self, token_id: &TokenId) -> AccountId {
+        self.tokens
+            .owner_by_id
+            .get(token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"))
+    }
+
+    fn battle_power(&self, token_id: &TokenId) -> u64 {
+        let stats = self
+            .warrior_stats
+            .get(token_id)
+            .unwrap_or_else(|| env::panic_str("Token has no stats"));
+        u64::from(stats.attack) + u64::from(stats.defense) / 2 + u64::from(stats.hp) / 10
+    }
+
+    /// Panics if `token_id` fought its last battle less than `BATTLE_COOLDOWN` ago. Only the
+    /// battles it chose to fight count: those it attacked in and the challenges it accepted.
+    fn assert_battle_ready(&self, token_id: &TokenId) {
+        if let Some(last) = self.battle_history.get(token_id).and_then(|history| {
+            history
+                .into_iter()
+                .rev()
+                .find(|record| record.attacker_token_id == *token_id || record.wager.0 > 0)
+        }) {
+            require!(
+                env::block_timestamp() >= last.timestamp.0.saturating_add(BATTLE_COOLDOWN),
+                format!("Token {} is on battle cooldown", token_id)
+            );
+        }
+    }
+
+    fn record_battle(&mut self, token_id: &TokenId, record: &BattleRecord) {
+        let mut history = self.battle_history.get(token_id).unwrap_or_default();
+        if history.len() == MAX_BATTLE_HISTORY {
+            history.remove(0);
+        }
+        history.push(record.clone());
+        self.battle_history.insert(token_id, &history);
+    }
+
+    /// Fights a battle, each warrior winning with a chance proportional to its power. The
+    /// attacker must be off its battle cooldown, and so must the defender of a wagered battle,
+    /// whose owner accepted it. The winner gains experience and the battle is recorded in the
+    /// history of both.
+    fn internal_battle(
+        &mut self,
+        attacker_token_id: TokenId,
+        defender_token_id: TokenId,
+        wager: Balance,
+    ) -> BattleRecord {
+        require!(
+            attacker_token_id != defender_token_id,
+            "A warrior cannot battle itself"
+        );
+        self.assert_not_paused();
+        self.assert_battle_ready(&attacker_token_id);
+        if wager > 0 {
+            self.assert_battle_ready(&defender_token_id);
+        }
+        let attacker_power = self.battle_power(&attacker_token_id);
+        let defender_power = self.battle_power(&defender_token_id);
+        let roll =
+            random_u64(format!("battle:{}:{}", attacker_token_id, defender_token_id).as_bytes())
+                % (attacker_power + defender_power);
+        let winner_token_id = if roll < attacker_power {
+            attacker_token_id.clone()
+        } else {
+            defender_token_id.clone()
+        };
+        self.internal_gain_xp(&winner_token_id, XP_PER_VICTORY);
+
+        let record = BattleRecord {
+            attacker_token_id,
+            defender_token_id,
+            winner_token_id,
+            wager: wager.into(),
+            timestamp: env::block_timestamp().into(),
+        };
+        self.record_battle(&record.attacker_token_id, &record);
+        self.record_battle(&record.defender_token_id, &record);
+        record
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Challenge another warrior to a battle without a wager, resolved immediately. The
+    /// defender did not consent to it, so it does not put the defender on battle cooldown.
+    /// The attached deposit must cover the storage of the battle history.
+    #[payable]
+    pub fn battle(
+        &mut self,
+        attacker_token_id: TokenId,
+        defender_token_id: TokenId,
+    ) -> BattleRecord {
+        let initial_storage_usage = env::storage_usage();
+        let challenger_id = env::predecessor_account_id();
+        require!(
+            self.expect_token_owner(&attacker_token_id) == challenger_id,
+            "Predecessor must own the attacker"
+        );
+        let defender_owner_id = self.expect_token_owner(&defender_token_id);
+        require!(
+            defender_owner_id != challenger_id,
+            "Cannot battle your own warrior"
+        );
+        self.assert_not_blocked(&challenger_id);
+        self.assert_not_blocked(&defender_owner_id);
+        let record = self.internal_battle(attacker_token_id, defender_token_id, 0);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        record
+    }
+
+    /// Offer a battle with a wager of `wager` yoctoNEAR, escrowed from the attached deposit
+    /// along with the storage of the challenge. Returns the challenge id.
+    #[payable]
+    pub fn challenge(
+        &mut self,
+        attacker_token_id: TokenId,
+        defender_token_id: TokenId,
+        wager: U128,
+    ) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        let challenger_id = env::predecessor_account_id();
+        require!(
+            self.expect_token_owner(&attacker_token_id) == challenger_id,
+            "Predecessor must own the attacker"
+        );
+        require!(
+            self.expect_token_owner(&defender_token_id) != challenger_id,
+            "Cannot battle your own warrior"
+        );
+        require!(wager.0 > 0, "Wager must be positive");
+        self.assert_not_paused();
+        self.assert_not_blocked(&challenger_id);
+
+        let challenge_id = self.next_challenge_id;
+        self.next_challenge_id += 1;
+        self.challenges.insert(
+            &challenge_id,
+            &Challenge {
+                challenger_id,
+                attacker_token_id,
+                defender_token_id,
+                wager,
+            },
+        );
+        refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            wager.0,
+        );
+        challenge_id
+    }
+
+    /// Accept a challenge against a warrior of the predecessor by matching its wager. The
+    /// battle is fought right away and the owner of the winner takes the pot.
+    #[payable]
+    pub fn accept_challenge(&mut self, challenge_id: u64) -> BattleRecord {
+        let initial_storage_usage = env::storage_usage();
+        let challenge = self
+            .challenges
+            .remove(&challenge_id)
+            .unwrap_or_else(|| env::panic_str("Challenge not found"));
+        require!(
+            self.expect_token_owner(&challenge.defender_token_id) == env::predecessor_account_id(),
+            "Predecessor must own the defender"
+        );
+        require!(
+            self.expect_token_owner(&challenge.attacker_token_id) == challenge.challenger_id,
+            "Challenger no longer owns the attacker"
+        );
+        self.assert_not_blocked(&env::predecessor_account_id());
+        self.assert_not_blocked(&challenge.challenger_id);
+
+        let wager: Balance = challenge.wager.into();
+        let record = self.internal_battle(
+            challenge.attacker_token_id,
+            challenge.defender_token_id,
+            wager,
+        );
+        let winner_id = self.expect_token_owner(&record.winner_token_id);
+        Promise::new(winner_id).transfer(2 * wager);
+
+        refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            wager,
+        );
+        record
+    }
+
+    /// Withdraw a challenge, or decline one against your warrior, refunding the wager.
+    #[payable]
+    pub fn cancel_challenge(&mut self, challenge_id: u64) {
+        assert_one_yocto();
+        let challenge = self
+            .challenges
+            .get(&challenge_id)
+            .unwrap_or_else(|| env::panic_str("Challenge not found"));
+        let predecessor_id = env::predecessor_account_id();
+        require!(
+            predecessor_id == challenge.challenger_id
+                || self.tokens.owner_by_id.get(&challenge.defender_token_id)
+                    == Some(predecessor_id),
+            "Only the challenger or the owner of the defender can cancel a challenge"
+        );
+        self.challenges.remove(&challenge_id);
+        Promise::new(challenge.challenger_id).transfer(challenge.wager.into());
+    }
+
+    pub fn get_challenge(&self, challenge_id: u64) -> Option<Challenge> {
+        self.challenges.get(&challenge_id)
+    }
+
+    /// Returns the most recent battles of a warrior, oldest first.
+    pub fn get_battle_history(&self, token_id: TokenId) -> Vec<BattleRecord> {
+        self.battle_history.get(&token_id).unwrap_or_default()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const BATTLE_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+    const WAGER: u128 = 1_000_000_000_000_000_000_000_000;
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let attacker = contract.nft_mint_default(accounts(1)).token_id;
+        let defender = contract.nft_mint_default(accounts(2)).token_id;
+        (context, contract, attacker, defender)
+    }
+
+    #[test]
+    fn test_battle_awards_xp_and_records_history() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let record = contract.battle(attacker.clone(), defender.clone());
+        assert!(record.winner_token_id == attacker || record.winner_token_id == defender);
+        assert_eq!(
+            contract
+                .nft_stats(record.winner_token_id.clone())
+                .unwrap()
+                .xp,
+            50
+        );
+        assert_eq!(contract.get_battle_history(attacker), vec![record.clone()]);
+        assert_eq!(contract.get_battle_history(defender), vec![record]);
+    }
+
+    #[test]
+    fn test_wagered_challenge() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(WAGER + BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let challenge_id = contract.challenge(attacker, defender, U128(WAGER));
+        assert!(contract.get_challenge(challenge_id).is_some());
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(2))
+            .build());
+        let record = contract.accept_challenge(challenge_id);
+        assert_eq!(record.wager, U128(WAGER));
+        assert!(contract.get_challenge(challenge_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "is on battle cooldown")]
+    fn test_battle_cooldown() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.battle(attacker.clone(), defender.clone());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(super::BATTLE_COOLDOWN - 1)
+            .build());
+        contract.battle(attacker, defender);
+    }
+
+    #[test]
+    fn test_free_battle_does_not_rest_defender() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.battle(attacker.clone(), defender.clone());
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(WAGER + BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(super::BATTLE_COOLDOWN)
+            .build());
+        let challenge_id = contract.challenge(attacker, defender.clone(), U128(WAGER));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.accept_challenge(challenge_id);
+        assert_eq!(contract.get_battle_history(defender).len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "is on battle cooldown")]
+    fn test_accepted_challenge_rests_defender() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        let other_attacker = contract.nft_mint_default(accounts(3)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(WAGER + BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let first = contract.challenge(attacker, defender.clone(), U128(WAGER));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(3))
+            .build());
+        let second = contract.challenge(other_attacker, defender, U128(WAGER));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.accept_challenge(first);
+        contract.accept_challenge(second);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn test_battle_while_paused() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        contract.pause();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.battle(attacker, defender);
+    }
+
+    #[test]
+    #[should_panic(expected = "is blocked")]
+    fn test_battle_blocked_defender() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        contract.add_to_blocklist(accounts(2));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.battle(attacker, defender);
+    }
+
+    #[test]
+    #[should_panic(expected = "Predecessor must own the attacker")]
+    fn test_battle_with_foreign_attacker() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.battle(attacker, defender);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn test_accept_challenge_without_matching_wager() {
+        let (mut context, mut contract, attacker, defender) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(WAGER + BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let challenge_id = contract.challenge(attacker, defender, U128(WAGER));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(BATTLE_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.accept_challenge(challenge_id);
+    }
+}
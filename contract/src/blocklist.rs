@@ -0,0 +1,184 @@
+//! Accounts blocked by admins, such as compromised or sanctioned ones. A blocked account cannot
+//! mint, send or receive tokens or vault shares, approve or be approved, equip or unequip, or
+//! take part in the rental, vault and lending markets, battles, staking, distribution rounds
+//! and governance votes.
+use crate::events::emit_contract_event;
+use crate::roles::Role;
+use crate::*;
+use near_sdk::serde::Serialize;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct BlocklistChange<'a> {
+    account_id: &'a AccountId,
+}
+
+impl Contract {
+    pub(crate) fn assert_not_blocked(&self, account_id: &AccountId) {
+        require!(
+            !self.blocklist.contains(account_id),
+            format!("Account {} is blocked", account_id)
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn add_to_blocklist(&mut self, account_id: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Admin);
+        if self.blocklist.insert(&account_id) {
+            emit_contract_event(
+                "account_blocked",
+                BlocklistChange {
+                    account_id: &account_id,
+                },
+            );
+        }
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    pub fn remove_from_blocklist(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        if self.blocklist.remove(&account_id) {
+            emit_contract_event(
+                "account_unblocked",
+                BlocklistChange {
+                    account_id: &account_id,
+                },
+            );
+        }
+    }
+
+    pub fn is_blocked(&self, account_id: AccountId) -> bool {
+        self.blocklist.contains(&account_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::staking::{RewardMode, StakingConfig};
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApproval;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        contract.nft_mint("0".to_string(), accounts(1), sample_token_metadata());
+        testing_env!(context.storage_usage(env::storage_usage()).build());
+        contract.add_to_blocklist(accounts(2));
+        (context, contract)
+    }
+
+    #[test]
+    fn test_block_and_unblock() {
+        let (_, mut contract) = setup();
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"hellomint","version":"1.0.0","event":"account_blocked","data":[{"account_id":"charlie"}]}"#
+        );
+        assert!(contract.is_blocked(accounts(2)));
+        contract.remove_from_blocklist(accounts(2));
+        assert!(!contract.is_blocked(accounts(2)));
+        assert!(get_logs().last().unwrap().contains("account_unblocked"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Account charlie is blocked")]
+    fn test_blocked_receiver() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account charlie is blocked")]
+    fn test_blocked_approval() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_approve("0".to_string(), accounts(2), None);
+    }
+
+    /// Blocks `accounts(1)`, the holder of token "0", after it took part in something.
+    fn block_holder(context: &mut VMContextBuilder, contract: &mut Contract) {
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.add_to_blocklist(accounts(1));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob is blocked")]
+    fn test_blocked_voter() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .block_index(1)
+            .build());
+        let proposal_id = contract.create_proposal(
+            "Raise the mint price?".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
+            U64(0),
+            U64(200),
+        );
+        block_holder(&mut context, &mut contract);
+        contract.vote(proposal_id, vec!["0".to_string()], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob is blocked")]
+    fn test_blocked_distribution_claimer() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1_000)
+            .block_index(1)
+            .build());
+        let round_id = contract.create_distribution();
+        block_holder(&mut context, &mut contract);
+        contract.claim(round_id, vec!["0".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob is blocked")]
+    fn test_blocked_staker_cannot_claim_rewards() {
+        let (mut context, mut contract) = setup();
+        contract.set_staking_config(Some(StakingConfig {
+            rates: vec![U128(10); 4],
+            reward_mode: RewardMode::Near,
+        }));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.stake("0".to_string());
+        block_holder(&mut context, &mut contract);
+        testing_env!(context.attached_deposit(1).build());
+        contract.claim_rewards();
+    }
+}
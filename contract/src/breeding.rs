@@ -0,0 +1,239 @@
+use crate::roles::Role;
+use crate::utils::refund_deposit_with_price;
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Balance;
+
+/// Terms of breeding, set by treasurers.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BreedingConfig {
+    /// Paid to the contract owner for every child.
+    pub fee: U128,
+    /// Nanoseconds a parent has to wait before breeding again.
+    pub cooldown: U64,
+    /// How many children a warrior can have.
+    pub max_breed_count: u32,
+}
+
+/// Provenance of a warrior. Warriors without a recorded lineage are of generation 0 and have
+/// never bred.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Lineage {
+    pub parent_ids: Vec<TokenId>,
+    pub generation: u32,
+    pub breed_count: u32,
+    /// Block timestamp from which the warrior can breed again.
+    pub cooldown_ends: U64,
+}
+
+impl Default for Lineage {
+    fn default() -> Self {
+        Self {
+            parent_ids: vec![],
+            generation: 0,
+            breed_count: 0,
+            cooldown_ends: U64(0),
+        }
+    }
+}
+
+impl Contract {
+    /// Checks that `token_id` belongs to `owner_id` and is ready to breed, and returns its
+    /// lineage updated for one more child.
+    fn expect_ready_to_breed(
+        &self,
+        token_id: &TokenId,
+        owner_id: &AccountId,
+        config: &BreedingConfig,
+    ) -> Lineage {
+        require!(
+            self.tokens.owner_by_id.get(token_id).as_ref() == Some(owner_id),
+            "Predecessor must own both parents"
+        );
+        let mut lineage = self.lineage.get(token_id).unwrap_or_default();
+        let now = env::block_timestamp();
+        require!(
+            lineage.cooldown_ends.0 <= now,
+            format!("Token {} is on breeding cooldown", token_id)
+        );
+        require!(
+            lineage.breed_count < config.max_breed_count,
+            format!("Token {} has reached its breed count limit", token_id)
+        );
+        lineage.breed_count += 1;
+        lineage.cooldown_ends = (now + config.cooldown.0).into();
+        lineage
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Enable breeding on the given terms, or disable it with `None`.
+    pub fn set_breeding_config(&mut self, config: Option<BreedingConfig>) {
+        self.assert_role(Role::Treasurer);
+        self.breeding_config = config;
+    }
+
+    pub fn get_breeding_config(&self) -> Option<BreedingConfig> {
+        self.breeding_config.clone()
+    }
+
+    /// Breed two warriors of the predecessor into a child whose traits are inherited from
+    /// them. The attached deposit must cover the breeding fee and the storage of the child.
+    #[payable]
+    pub fn breed(&mut self, parent_a_id: TokenId, parent_b_id: TokenId) -> Token {
+        let initial_storage_usage = env::storage_usage();
+        let config = self
+            .breeding_config
+            .clone()
+            .unwrap_or_else(|| env::panic_str("Breeding is not enabled"));
+        require!(
+            parent_a_id != parent_b_id,
+            "A warrior cannot breed with itself"
+        );
+        let owner_id = env::predecessor_account_id();
+        let lineage_a = self.expect_ready_to_breed(&parent_a_id, &owner_id, &config);
+        let lineage_b = self.expect_ready_to_breed(&parent_b_id, &owner_id, &config);
+        let traits_a = self.token_traits.get(&parent_a_id);
+        let traits_b = self.token_traits.get(&parent_b_id);
+        let (traits_a, traits_b) = traits_a
+            .zip(traits_b)
+            .unwrap_or_else(|| env::panic_str("Both parents must have traits"));
+
+        let token_id = format!("bred-{}", self.bred_count);
+        self.bred_count += 1;
+        self.token_traits.insert(
+            &token_id,
+            &svg::WarriorTraits::inherit(&traits_a, &traits_b, &token_id),
+        );
+        self.warrior_stats
+            .insert(&token_id, &stats::WarriorStats::random(&token_id));
+        self.lineage.insert(
+            &token_id,
+            &Lineage {
+                generation: lineage_a.generation.max(lineage_b.generation) + 1,
+                parent_ids: vec![parent_a_id.clone(), parent_b_id.clone()],
+                ..Default::default()
+            },
+        );
+        self.lineage.insert(&parent_a_id, &lineage_a);
+        self.lineage.insert(&parent_b_id, &lineage_b);
+
+        let token = self.internal_mint_token(
+            token_id,
+            owner_id,
+            TokenMetadata {
+                media: None,
+                ..Self::token_metadata()
+            },
+        );
+        let fee: Balance = config.fee.into();
+        if fee > 0 {
+            Promise::new(self.tokens.owner_id.clone()).transfer(fee);
+        }
+        refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            fee,
+        );
+        token
+    }
+
+    /// Returns the parents, generation and breeding state of a warrior.
+    pub fn nft_lineage(&self, token_id: TokenId) -> Option<Lineage> {
+        self.tokens.owner_by_id.get(&token_id)?;
+        Some(self.lineage.get(&token_id).unwrap_or_default())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::BreedingConfig;
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const FEE: u128 = 1_000_000_000_000_000_000_000_000;
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        contract.set_breeding_config(Some(BreedingConfig {
+            fee: U128(FEE),
+            cooldown: U64(1_000),
+            max_breed_count: 2,
+        }));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let parent_a = contract.nft_mint_default(accounts(1)).token_id;
+        let parent_b = contract.nft_mint_default(accounts(1)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(FEE + MINT_DEPOSIT)
+            .block_timestamp(100)
+            .build());
+        (context, contract, parent_a, parent_b)
+    }
+
+    #[test]
+    fn test_breed_records_lineage() {
+        let (_, mut contract, parent_a, parent_b) = setup();
+        let child = contract.breed(parent_a.clone(), parent_b.clone());
+        assert_eq!(child.owner_id, accounts(1));
+        assert!(contract.nft_traits(child.token_id.clone()).is_some());
+
+        let lineage = contract.nft_lineage(child.token_id).unwrap();
+        assert_eq!(lineage.parent_ids, vec![parent_a.clone(), parent_b]);
+        assert_eq!(lineage.generation, 1);
+        let lineage = contract.nft_lineage(parent_a).unwrap();
+        assert_eq!(lineage.generation, 0);
+        assert_eq!(lineage.breed_count, 1);
+        assert_eq!(lineage.cooldown_ends, U64(1_100));
+    }
+
+    #[test]
+    #[should_panic(expected = "is on breeding cooldown")]
+    fn test_breed_during_cooldown() {
+        let (mut context, mut contract, parent_a, parent_b) = setup();
+        contract.breed(parent_a.clone(), parent_b.clone());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(1_099)
+            .build());
+        contract.breed(parent_a, parent_b);
+    }
+
+    #[test]
+    #[should_panic(expected = "has reached its breed count limit")]
+    fn test_breed_count_limit() {
+        let (mut context, mut contract, parent_a, parent_b) = setup();
+        for timestamp in &[1_100, 2_200] {
+            contract.breed(parent_a.clone(), parent_b.clone());
+            testing_env!(context
+                .storage_usage(env::storage_usage())
+                .block_timestamp(*timestamp)
+                .build());
+        }
+        contract.breed(parent_a, parent_b);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn test_breed_without_fee() {
+        let (mut context, mut contract, parent_a, parent_b) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        contract.breed(parent_a, parent_b);
+    }
+}
@@ -0,0 +1,396 @@
+//! Check-in of event tickets. Minters group tickets into event series and admins appoint
+//! verifiers. Holders register an ed25519 key from their account beforehand. At the gate, the
+//! verifier picks a fresh nonce and the holder signs the challenge `(series_id, token_id, nonce)`
+//! with that key, proving they control the owner of the ticket. The verifier then checks the
+//! ticket in with the signature. A ticket is checked in once, and tickets of series that lock
+//! after check-in cannot change hands anymore: they can neither be transferred nor equipped,
+//! escrowed for a loan or fractionalized.
+use crate::roles::Role;
+use crate::*;
+use ed25519_dalek::Verifier;
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::CurveType;
+use std::convert::TryFrom;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventSeries {
+    pub name: String,
+    /// Whether tickets become non-transferable once checked in.
+    pub lock_after_check_in: bool,
+    pub checked_in: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CheckIn {
+    pub holder_id: AccountId,
+    pub verifier_id: AccountId,
+    pub checked_in_at: U64,
+}
+
+/// The message a holder signs to check a ticket in: the borsh serialization of
+/// `(series_id, token_id, nonce)`.
+pub fn check_in_challenge(series_id: &str, token_id: &str, nonce: u64) -> Vec<u8> {
+    (series_id, token_id, nonce).try_to_vec().unwrap()
+}
+
+/// Whether `signature` is a valid ed25519 signature of `message` by `public_key`.
+fn verify_signature(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+    let public_key = match ed25519_dalek::PublicKey::from_bytes(&public_key.as_bytes()[1..]) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    ed25519_dalek::Signature::try_from(signature).map_or(false, |signature| {
+        public_key.verify(message, &signature).is_ok()
+    })
+}
+
+impl Contract {
+    fn assert_verifier(&self) {
+        require!(
+            self.verifiers.contains(&env::predecessor_account_id()),
+            "Only verifiers can check tickets in"
+        );
+    }
+
+    /// Panics if `token_id` is a checked-in ticket of a series that locks after check-in.
+    pub(crate) fn assert_not_checked_in(&self, token_id: &TokenId) {
+        if self.check_ins.get(token_id).is_none() {
+            return;
+        }
+        let locked = self
+            .ticket_series
+            .get(token_id)
+            .and_then(|series_id| self.event_series.get(&series_id))
+            .map_or(false, |series| series.lock_after_check_in);
+        require!(!locked, "Checked-in tickets cannot be transferred");
+    }
+
+    /// Forgets the check-in state of `token_id`. Used when it is burned.
+    pub(crate) fn internal_clear_check_in(&mut self, token_id: &TokenId) {
+        self.ticket_series.remove(token_id);
+        self.check_ins.remove(token_id);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn add_verifier(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        self.verifiers.insert(&account_id);
+    }
+
+    pub fn remove_verifier(&mut self, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        self.verifiers.remove(&account_id);
+    }
+
+    pub fn is_verifier(&self, account_id: AccountId) -> bool {
+        self.verifiers.contains(&account_id)
+    }
+
+    /// Create an event series, to which tickets are then added.
+    #[payable]
+    pub fn create_event_series(
+        &mut self,
+        series_id: String,
+        name: String,
+        lock_after_check_in: bool,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Minter);
+        require!(
+            self.event_series.get(&series_id).is_none(),
+            "Event series already exists"
+        );
+        self.event_series.insert(
+            &series_id,
+            &EventSeries {
+                name,
+                lock_after_check_in,
+                checked_in: 0,
+            },
+        );
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Make `token_ids` tickets of an event series. A token is a ticket of one series only.
+    #[payable]
+    pub fn add_tickets(&mut self, series_id: String, token_ids: Vec<TokenId>) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Minter);
+        require!(
+            self.event_series.get(&series_id).is_some(),
+            "Event series not found"
+        );
+        let mut tickets = self.series_tickets.get(&series_id).unwrap_or_default();
+        for token_id in token_ids {
+            require!(
+                self.tokens.owner_by_id.get(&token_id).is_some(),
+                "Token not found"
+            );
+            require!(
+                self.ticket_series.insert(&token_id, &series_id).is_none(),
+                format!("Token {} is already a ticket", token_id)
+            );
+            tickets.push(token_id);
+        }
+        self.series_tickets.insert(&series_id, &tickets);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Register the ed25519 key the predecessor signs check-in challenges with, replacing any
+    /// previous one.
+    #[payable]
+    pub fn register_ticket_key(&mut self, public_key: PublicKey) {
+        let initial_storage_usage = env::storage_usage();
+        require!(
+            public_key.curve_type() == CurveType::ED25519,
+            "Ticket key must be an ed25519 key"
+        );
+        self.ticket_keys
+            .insert(&env::predecessor_account_id(), &public_key);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    pub fn get_ticket_key(&self, account_id: AccountId) -> Option<PublicKey> {
+        self.ticket_keys.get(&account_id)
+    }
+
+    /// Check a ticket in, given the signature of its challenge for `nonce` by the key its owner
+    /// registered. Only verifiers can check tickets in, each ticket once, and within its
+    /// validity window.
+    pub fn check_in(&mut self, token_id: TokenId, nonce: U64, signature: Base64VecU8) -> CheckIn {
+        self.assert_verifier();
+        let series_id = self
+            .ticket_series
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token is not a ticket"));
+        require!(
+            self.check_ins.get(&token_id).is_none(),
+            "Ticket is already checked in"
+        );
+        self.assert_active(&token_id);
+        let owner_id = self.tokens.owner_by_id.get(&token_id).unwrap();
+        let public_key = self
+            .ticket_keys
+            .get(&owner_id)
+            .unwrap_or_else(|| env::panic_str("The holder has not registered a ticket key"));
+        require!(
+            verify_signature(
+                &public_key,
+                &check_in_challenge(&series_id, &token_id, nonce.0),
+                &signature.0
+            ),
+            "Challenge is not signed by the holder"
+        );
+
+        let check_in = CheckIn {
+            holder_id: owner_id,
+            verifier_id: env::predecessor_account_id(),
+            checked_in_at: env::block_timestamp().into(),
+        };
+        self.check_ins.insert(&token_id, &check_in);
+        let mut series = self.event_series.get(&series_id).unwrap();
+        series.checked_in += 1;
+        self.event_series.insert(&series_id, &series);
+        check_in
+    }
+
+    pub fn get_check_in(&self, token_id: TokenId) -> Option<CheckIn> {
+        self.check_ins.get(&token_id)
+    }
+
+    pub fn get_event_series(&self, series_id: String) -> Option<EventSeries> {
+        self.event_series.get(&series_id)
+    }
+
+    /// Returns the tickets of an event series with their check-in, if checked in.
+    pub fn get_series_check_ins(
+        &self,
+        series_id: String,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<(TokenId, Option<CheckIn>)> {
+        let start = from_index.map_or(0, |index| index.0);
+        let limit = limit.unwrap_or(u64::MAX);
+        self.series_tickets
+            .get(&series_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|token_id| self.ticket_series.get(token_id).as_ref() == Some(&series_id))
+            .skip(start as usize)
+            .take(limit as usize)
+            .map(|token_id| {
+                let check_in = self.check_ins.get(&token_id);
+                (token_id, check_in)
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::check_in_challenge;
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use ed25519_dalek::{ExpandedSecretKey, SecretKey};
+    use near_contract_standards::fungible_token::metadata::{
+        FungibleTokenMetadata, FT_METADATA_SPEC,
+    };
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{Base64VecU8, U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, CurveType};
+    use std::convert::TryFrom;
+
+    const HOLDER_SECRET: [u8; 32] = [7; 32];
+
+    fn sign(secret: [u8; 32], nonce: u64) -> Base64VecU8 {
+        let secret = SecretKey::from_bytes(&secret).unwrap();
+        let public_key = ed25519_dalek::PublicKey::from(&secret);
+        let message = check_in_challenge("gig", "ticket", nonce);
+        let signature = ExpandedSecretKey::from(&secret).sign(&message, &public_key);
+        Base64VecU8(signature.to_bytes().to_vec())
+    }
+
+    fn setup(lock_after_check_in: bool) -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        contract.nft_mint("ticket".to_string(), accounts(1), sample_token_metadata());
+        testing_env!(context.storage_usage(env::storage_usage()).build());
+        contract.create_event_series("gig".to_string(), "Gig".to_string(), lock_after_check_in);
+        contract.add_tickets("gig".to_string(), vec!["ticket".to_string()]);
+        contract.add_verifier(accounts(3));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        let secret = SecretKey::from_bytes(&HOLDER_SECRET).unwrap();
+        let public_key = ed25519_dalek::PublicKey::from(&secret);
+        let mut key_bytes = vec![CurveType::ED25519 as u8];
+        key_bytes.extend_from_slice(public_key.as_bytes());
+        contract.register_ticket_key(PublicKey::try_from(key_bytes).unwrap());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(100)
+            .build());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_check_in() {
+        let (_, mut contract) = setup(false);
+        contract.check_in("ticket".to_string(), U64(42), sign(HOLDER_SECRET, 42));
+        assert_eq!(
+            contract
+                .get_check_in("ticket".to_string())
+                .unwrap()
+                .checked_in_at,
+            U64(100)
+        );
+        assert_eq!(
+            contract
+                .get_event_series("gig".to_string())
+                .unwrap()
+                .checked_in,
+            1
+        );
+        let check_ins = contract.get_series_check_ins("gig".to_string(), None, None);
+        assert_eq!(check_ins.len(), 1);
+        assert_eq!(check_ins[0].1.as_ref().unwrap().holder_id, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Challenge is not signed by the holder")]
+    fn test_check_in_with_wrong_nonce() {
+        let (_, mut contract) = setup(false);
+        contract.check_in("ticket".to_string(), U64(7), sign(HOLDER_SECRET, 42));
+    }
+
+    #[test]
+    #[should_panic(expected = "Challenge is not signed by the holder")]
+    fn test_check_in_with_other_key() {
+        let (_, mut contract) = setup(false);
+        contract.check_in("ticket".to_string(), U64(42), sign([8; 32], 42));
+    }
+
+    #[test]
+    #[should_panic(expected = "Checked-in tickets cannot be transferred")]
+    fn test_locked_after_check_in() {
+        let (mut context, mut contract) = setup(true);
+        contract.check_in("ticket".to_string(), U64(42), sign(HOLDER_SECRET, 42));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(2), "ticket".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Checked-in tickets cannot be transferred")]
+    fn test_equip_checked_in_ticket() {
+        let (mut context, mut contract) = setup(true);
+        contract.check_in("ticket".to_string(), U64(42), sign(HOLDER_SECRET, 42));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.nft_mint("warrior".to_string(), accounts(1), sample_token_metadata());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.equip("warrior".to_string(), "ticket".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Checked-in tickets cannot be transferred")]
+    fn test_loan_against_checked_in_ticket() {
+        let (mut context, mut contract) = setup(true);
+        contract.check_in("ticket".to_string(), U64(42), sign(HOLDER_SECRET, 42));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.request_loan("ticket".to_string(), U128(1_000), U128(100), U64(50));
+    }
+
+    #[test]
+    #[should_panic(expected = "Checked-in tickets cannot be transferred")]
+    fn test_fractionalize_checked_in_ticket() {
+        let (mut context, mut contract) = setup(true);
+        contract.check_in("ticket".to_string(), U64(42), sign(HOLDER_SECRET, 42));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.fractionalize(
+            "ticket".to_string(),
+            U128(100),
+            U128(1_000),
+            FungibleTokenMetadata {
+                spec: FT_METADATA_SPEC.to_string(),
+                name: "Gig ticket shares".to_string(),
+                symbol: "GIG".to_string(),
+                icon: None,
+                reference: None,
+                reference_hash: None,
+                decimals: 0,
+            },
+        );
+    }
+}
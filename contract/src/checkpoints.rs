@@ -0,0 +1,239 @@
+//! Ownership checkpoints. Every change of owner is recorded with the block height it happened
+//! at, so that the owner of a token and the number of tokens of an account can be looked up as
+//! they were at the end of any past block.
+//!
+//! The checkpoints of each token and each account are kept in a vector of their own, so that
+//! recording one only appends to it and a lookup binary searches it. Their storage is charged
+//! to the attached deposit of the calls that take one, such as mints and moves into custody.
+//! Plain NEP-171 transfers take exactly one yoctoNEAR, so the contract covers theirs.
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+
+/// Value as of the end of `block_height`, given checkpoints in increasing height order.
+fn value_at<T: BorshSerialize + BorshDeserialize>(
+    checkpoints: &Vector<(u64, T)>,
+    block_height: u64,
+) -> Option<T> {
+    // number of checkpoints recorded at or before `block_height`
+    let (mut low, mut high) = (0, checkpoints.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if checkpoints.get(mid).unwrap().0 <= block_height {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low.checked_sub(1)
+        .map(|index| checkpoints.get(index).unwrap().1)
+}
+
+fn last_checkpoint<T: BorshSerialize + BorshDeserialize>(
+    checkpoints: &Vector<(u64, T)>,
+) -> Option<(u64, T)> {
+    checkpoints
+        .len()
+        .checked_sub(1)
+        .map(|index| checkpoints.get(index).unwrap())
+}
+
+/// Records `value` from the current block on, replacing a checkpoint of the same block.
+fn push_checkpoint<T: BorshSerialize + BorshDeserialize>(
+    checkpoints: &mut Vector<(u64, T)>,
+    value: T,
+) {
+    let block_height = env::block_height();
+    match last_checkpoint(checkpoints) {
+        Some((height, _)) if height == block_height => {
+            checkpoints.replace(checkpoints.len() - 1, &(block_height, value));
+        }
+        _ => checkpoints.push(&(block_height, value)),
+    }
+}
+
+impl Contract {
+    fn token_checkpoints(&self, token_id: &TokenId) -> Vector<(u64, Option<AccountId>)> {
+        self.owner_checkpoints.get(token_id).unwrap_or_else(|| {
+            Vector::new(StorageKey::OwnerCheckpointsOf {
+                token_hash: env::sha256_array(token_id.as_bytes()),
+            })
+        })
+    }
+
+    fn account_checkpoints(&self, account_id: &AccountId) -> Vector<(u64, u64)> {
+        self.supply_checkpoints.get(account_id).unwrap_or_else(|| {
+            Vector::new(StorageKey::SupplyCheckpointsOf {
+                account_hash: env::sha256_array(account_id.as_bytes()),
+            })
+        })
+    }
+
+    fn add_to_supply_checkpoint(&mut self, account_id: &AccountId, increment: bool) {
+        let mut checkpoints = self.account_checkpoints(account_id);
+        let supply = last_checkpoint(&checkpoints).map_or(0, |(_, supply)| supply);
+        let supply = if increment { supply + 1 } else { supply - 1 };
+        push_checkpoint(&mut checkpoints, supply);
+        self.supply_checkpoints.insert(account_id, &checkpoints);
+    }
+
+    /// Records tokens which existed before checkpoints were kept as owned by their current
+    /// owner since the first block.
+    pub(crate) fn internal_seed_checkpoints(&mut self, owners: &[(TokenId, AccountId)]) {
+        let mut supplies: HashMap<&AccountId, u64> = HashMap::new();
+        for (token_id, owner_id) in owners {
+            let mut checkpoints = self.token_checkpoints(token_id);
+            checkpoints.push(&(0, Some(owner_id.clone())));
+            self.owner_checkpoints.insert(token_id, &checkpoints);
+            *supplies.entry(owner_id).or_default() += 1;
+        }
+        for (owner_id, supply) in supplies {
+            let mut checkpoints = self.account_checkpoints(owner_id);
+            checkpoints.push(&(0, supply));
+            self.supply_checkpoints.insert(owner_id, &checkpoints);
+        }
+    }
+
+    /// Records that `token_id` moved from `from` to `to`, `None` standing for a mint or a burn.
+    pub(crate) fn internal_checkpoint_owner(
+        &mut self,
+        token_id: &TokenId,
+        from: Option<&AccountId>,
+        to: Option<&AccountId>,
+    ) {
+        let mut checkpoints = self.token_checkpoints(token_id);
+        push_checkpoint(&mut checkpoints, to.cloned());
+        self.owner_checkpoints.insert(token_id, &checkpoints);
+        if let Some(from) = from {
+            self.add_to_supply_checkpoint(from, false);
+        }
+        if let Some(to) = to {
+            self.add_to_supply_checkpoint(to, true);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns the owner of `token_id` at the end of `block_height`, or `None` if the token
+    /// did not exist then.
+    pub fn nft_owner_at(&self, token_id: TokenId, block_height: U64) -> Option<AccountId> {
+        let checkpoints = self.owner_checkpoints.get(&token_id)?;
+        value_at(&checkpoints, block_height.0).flatten()
+    }
+
+    /// Returns the number of tokens `account_id` owned at the end of `block_height`.
+    pub fn nft_supply_for_owner_at(&self, account_id: AccountId, block_height: U64) -> U128 {
+        let supply = self
+            .supply_checkpoints
+            .get(&account_id)
+            .and_then(|checkpoints| value_at(&checkpoints, block_height.0))
+            .unwrap_or(0);
+        U128(supply.into())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_STORAGE_COST};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_owner_and_supply_at() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .block_index(10)
+            .build());
+        contract.nft_mint("0".to_string(), accounts(0), sample_token_metadata());
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .block_index(20)
+            .build());
+        contract.nft_transfer(accounts(1), "0".to_string(), None, None);
+
+        let token_id = "0".to_string();
+        assert_eq!(contract.nft_owner_at(token_id.clone(), U64(9)), None);
+        assert_eq!(
+            contract.nft_owner_at(token_id.clone(), U64(19)),
+            Some(accounts(0))
+        );
+        assert_eq!(contract.nft_owner_at(token_id, U64(20)), Some(accounts(1)));
+        assert_eq!(
+            contract.nft_supply_for_owner_at(accounts(0), U64(15)),
+            U128(1)
+        );
+        assert_eq!(
+            contract.nft_supply_for_owner_at(accounts(0), U64(20)),
+            U128(0)
+        );
+        assert_eq!(
+            contract.nft_supply_for_owner_at(accounts(1), U64(20)),
+            U128(1)
+        );
+    }
+
+    #[test]
+    fn test_lookup_across_many_checkpoints() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .block_index(10)
+            .build());
+        contract.nft_mint("0".to_string(), accounts(0), sample_token_metadata());
+
+        // the token changes hands two or three times in each block
+        let mut owner = accounts(0);
+        let mut owners = vec![];
+        for block in 1..=9 {
+            for _ in 0..(2 + block % 2) {
+                let receiver = if owner == accounts(0) {
+                    accounts(1)
+                } else {
+                    accounts(0)
+                };
+                testing_env!(context
+                    .storage_usage(env::storage_usage())
+                    .attached_deposit(1)
+                    .block_index(10 + block * 10)
+                    .predecessor_account_id(owner)
+                    .build());
+                contract.nft_transfer(receiver.clone(), "0".to_string(), None, None);
+                owner = receiver;
+            }
+            owners.push(owner.clone());
+        }
+
+        assert_eq!(
+            contract.nft_owner_at("0".to_string(), U64(19)),
+            Some(accounts(0))
+        );
+        for (block, owner) in (1..=9).zip(owners) {
+            let height = U64(10 + block * 10);
+            assert_eq!(
+                contract.nft_owner_at("0".to_string(), height),
+                Some(owner.clone())
+            );
+            assert_eq!(contract.nft_supply_for_owner_at(owner, height), U128(1));
+        }
+        assert_eq!(
+            contract
+                .owner_checkpoints
+                .get(&"0".to_string())
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use crate::roles::Role;
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// What a token has to be to be used as an input of a recipe. Criteria left out match any token.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InputCriteria {
+    /// The series of the token, matched against the title of its metadata.
+    pub title: Option<String>,
+    pub min_level: Option<u32>,
+    pub background: Option<u8>,
+    pub skin: Option<u8>,
+    pub armor: Option<u8>,
+    pub weapon: Option<u8>,
+    pub helmet: Option<u8>,
+}
+
+/// Burns one token matching each of `inputs` to mint a token with the `output` metadata.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Recipe {
+    pub inputs: Vec<InputCriteria>,
+    pub output: TokenMetadata,
+}
+
+impl Contract {
+    fn matches_criteria(&self, token_id: &TokenId, criteria: &InputCriteria) -> bool {
+        if let Some(title) = &criteria.title {
+            let metadata = self
+                .tokens
+                .token_metadata_by_id
+                .as_ref()
+                .and_then(|by_id| by_id.get(token_id));
+            if metadata.and_then(|metadata| metadata.title).as_ref() != Some(title) {
+                return false;
+            }
+        }
+        if let Some(min_level) = criteria.min_level {
+            if self
+                .warrior_stats
+                .get(token_id)
+                .map_or(true, |stats| stats.level < min_level)
+            {
+                return false;
+            }
+        }
+        let trait_criteria = [
+            criteria.background,
+            criteria.skin,
+            criteria.armor,
+            criteria.weapon,
+            criteria.helmet,
+        ];
+        if trait_criteria.iter().all(Option::is_none) {
+            return true;
+        }
+        self.token_traits.get(token_id).map_or(false, |traits| {
+            let layers = [
+                traits.background,
+                traits.skin,
+                traits.armor,
+                traits.weapon,
+                traits.helmet,
+            ];
+            trait_criteria
+                .iter()
+                .zip(layers.iter())
+                .all(|(criterion, layer)| criterion.map_or(true, |value| value == *layer))
+        })
+    }
+
+    /// Stats of a crafted warrior: the summed attack, defense and hp of its inputs, at the
+    /// level of the most experienced one.
+    fn combine_stats(&self, token_ids: &[TokenId]) -> Option<stats::WarriorStats> {
+        token_ids
+            .iter()
+            .filter_map(|token_id| self.warrior_stats.get(token_id))
+            .reduce(|combined, stats| stats::WarriorStats {
+                attack: combined.attack.saturating_add(stats.attack),
+                defense: combined.defense.saturating_add(stats.defense),
+                hp: combined.hp.saturating_add(stats.hp),
+                xp: combined.xp.max(stats.xp),
+                level: combined.level.max(stats.level),
+            })
+    }
+
+    /// Traits of a crafted warrior: the highest of each layer among its inputs.
+    fn combine_traits(&self, token_ids: &[TokenId]) -> Option<svg::WarriorTraits> {
+        token_ids
+            .iter()
+            .filter_map(|token_id| self.token_traits.get(token_id))
+            .reduce(|combined, traits| svg::WarriorTraits {
+                background: combined.background.max(traits.background),
+                skin: combined.skin.max(traits.skin),
+                armor: combined.armor.max(traits.armor),
+                weapon: combined.weapon.max(traits.weapon),
+                helmet: combined.helmet.max(traits.helmet),
+            })
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Register a recipe and return its id.
+    pub fn add_recipe(&mut self, recipe: Recipe) -> u64 {
+        self.assert_role(Role::Admin);
+        require!(!recipe.inputs.is_empty(), "Recipe must have inputs");
+        let recipe_id = self.next_recipe_id;
+        self.next_recipe_id += 1;
+        self.recipes.insert(&recipe_id, &recipe);
+        recipe_id
+    }
+
+    pub fn remove_recipe(&mut self, recipe_id: u64) {
+        self.assert_role(Role::Admin);
+        require!(
+            self.recipes.remove(&recipe_id).is_some(),
+            "Recipe not found"
+        );
+    }
+
+    pub fn get_recipe(&self, recipe_id: u64) -> Option<Recipe> {
+        self.recipes.get(&recipe_id)
+    }
+
+    /// Burn `input_token_ids`, matched in order against the inputs of the recipe, to mint the
+    /// output of the recipe with their combined stats and traits. The storage freed by the
+    /// burned tokens goes towards the storage of the new one, and only the difference, if any,
+    /// has to be covered by the attached deposit.
+    #[payable]
+    pub fn craft(&mut self, recipe_id: u64, input_token_ids: Vec<TokenId>) -> Token {
+        let initial_storage_usage = env::storage_usage();
+        let recipe = self
+            .recipes
+            .get(&recipe_id)
+            .unwrap_or_else(|| env::panic_str("Recipe not found"));
+        require!(
+            input_token_ids.len() == recipe.inputs.len(),
+            format!("Recipe takes {} input tokens", recipe.inputs.len())
+        );
+        let crafter_id = env::predecessor_account_id();
+        for (i, (token_id, criteria)) in input_token_ids.iter().zip(&recipe.inputs).enumerate() {
+            require!(
+                !input_token_ids[..i].contains(token_id),
+                "Input tokens must be distinct"
+            );
+            require!(
+                self.tokens.owner_by_id.get(token_id) == Some(crafter_id.clone()),
+                "Predecessor must own the input tokens"
+            );
+            self.assert_not_staked(token_id);
+            require!(
+                self.matches_criteria(token_id, criteria),
+                format!("Token {} does not match the recipe", token_id)
+            );
+        }
+
+        let stats = self.combine_stats(&input_token_ids);
+        let traits = self.combine_traits(&input_token_ids);
+        for token_id in &input_token_ids {
+            self.internal_burn(token_id, Some("craft"));
+        }
+
+        let token_id = format!("crafted-{}", self.crafted_count);
+        self.crafted_count += 1;
+        if let Some(stats) = stats {
+            self.warrior_stats.insert(&token_id, &stats);
+        }
+        let token_metadata = match traits {
+            Some(traits) => {
+                self.token_traits.insert(&token_id, &traits);
+                TokenMetadata {
+                    media: None,
+                    media_hash: None,
+                    ..recipe.output
+                }
+            }
+            None => recipe.output,
+        };
+        let token = self.internal_mint_token(token_id, crafter_id, token_metadata);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        token
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{InputCriteria, Recipe};
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn any_warrior() -> InputCriteria {
+        InputCriteria {
+            title: Contract::token_metadata().title,
+            min_level: None,
+            background: None,
+            skin: None,
+            armor: None,
+            weapon: None,
+            helmet: None,
+        }
+    }
+
+    fn setup() -> (VMContextBuilder, Contract, u64, Vec<TokenId>) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        let recipe_id = contract.add_recipe(Recipe {
+            inputs: vec![any_warrior(), any_warrior()],
+            output: sample_token_metadata(),
+        });
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let token_ids = vec![
+            contract.nft_mint_default(accounts(1)).token_id,
+            contract.nft_mint_default(accounts(1)).token_id,
+        ];
+        (context, contract, recipe_id, token_ids)
+    }
+
+    #[test]
+    fn test_craft_burns_inputs_without_deposit() {
+        let (mut context, mut contract, recipe_id, token_ids) = setup();
+        let attack: u32 = token_ids
+            .iter()
+            .map(|token_id| contract.nft_stats(token_id.clone()).unwrap().attack)
+            .sum();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .build());
+        let token = contract.craft(recipe_id, token_ids.clone());
+        assert_eq!(token.owner_id, accounts(1));
+        assert_eq!(
+            contract.nft_stats(token.token_id.clone()).unwrap().attack,
+            attack
+        );
+        assert!(contract.nft_traits(token.token_id).is_some());
+        assert_eq!(contract.nft_total_supply(), U128(1));
+        assert!(contract.nft_token(token_ids[0].clone()).is_none());
+        assert!(contract.nft_stats(token_ids[1].clone()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Predecessor must own the input tokens")]
+    fn test_craft_foreign_tokens() {
+        let (mut context, mut contract, recipe_id, token_ids) = setup();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.craft(recipe_id, token_ids);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match the recipe")]
+    fn test_craft_unmatched_input() {
+        let (mut context, mut contract, _, token_ids) = setup();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let recipe_id = contract.add_recipe(Recipe {
+            inputs: vec![
+                any_warrior(),
+                InputCriteria {
+                    min_level: Some(2),
+                    ..any_warrior()
+                },
+            ],
+            output: sample_token_metadata(),
+        });
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.craft(recipe_id, token_ids);
+    }
+}
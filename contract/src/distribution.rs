@@ -0,0 +1,449 @@
+use crate::roles::Role;
+use crate::*;
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, ext_contract, Balance, Gas, PromiseResult};
+
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_CLAIM: Gas = Gas(10_000_000_000_000);
+/// Time tokens have to claim their share of a round, after which the rest can be swept.
+const CLAIM_PERIOD: u64 = 90 * 24 * 60 * 60 * 1_000_000_000;
+
+#[ext_contract(ext_self)]
+trait ClaimResolver {
+    fn resolve_distribution_claim(&mut self, round_id: u64, token_ids: Vec<TokenId>);
+    fn resolve_distribution_sweep(&mut self, round_id: u64, amount: U128);
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DistributionAsset {
+    Near,
+    /// A fungible token, by the account of its contract.
+    Ft(AccountId),
+}
+
+/// An amount shared equally between the tokens that existed when the round was created,
+/// leaving out those held in the custody of the contract.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Distribution {
+    pub asset: DistributionAsset,
+    pub amount: U128,
+    pub per_token: U128,
+    /// Block the round was created at. Only tokens which existed before this block can claim.
+    pub block_height: U64,
+    pub claimed: U128,
+    /// Block timestamp from which tokens cannot claim anymore and the rest can be swept.
+    pub claim_deadline: U64,
+}
+
+impl Contract {
+    fn internal_create_distribution(&mut self, asset: DistributionAsset, amount: Balance) -> u64 {
+        let custody = self
+            .tokens
+            .tokens_per_owner
+            .as_ref()
+            .and_then(|by_owner| by_owner.get(&env::current_account_id()))
+            .map_or(0, |tokens| tokens.len());
+        let supply = self.tokens.owner_by_id.len() - custody;
+        require!(supply > 0, "No tokens to distribute to");
+        let per_token = amount / Balance::from(supply);
+        require!(per_token > 0, "Amount is too small to distribute");
+
+        let round_id = self.next_distribution_id;
+        self.next_distribution_id += 1;
+        self.distributions.insert(
+            &round_id,
+            &Distribution {
+                asset,
+                amount: amount.into(),
+                per_token: per_token.into(),
+                block_height: env::block_height().into(),
+                claimed: U128(0),
+                claim_deadline: (env::block_timestamp() + CLAIM_PERIOD).into(),
+            },
+        );
+        round_id
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Open a distribution round of the attached NEAR, shared between the current tokens.
+    /// Returns the round id.
+    #[payable]
+    pub fn create_distribution(&mut self) -> u64 {
+        self.assert_role(Role::Treasurer);
+        self.internal_create_distribution(DistributionAsset::Near, env::attached_deposit())
+    }
+
+    /// Claim the share of a distribution round of each of `token_ids`, which the predecessor
+    /// must own. Each token can claim once per round, whoever owns it. The attached deposit
+    /// must cover the storage of the claims.
+    #[payable]
+    pub fn claim(&mut self, round_id: u64, token_ids: Vec<TokenId>) -> U128 {
+        let initial_storage_usage = env::storage_usage();
+        let mut round = self
+            .distributions
+            .get(&round_id)
+            .unwrap_or_else(|| env::panic_str("Distribution round not found"));
+        require!(
+            env::block_timestamp() < round.claim_deadline.0,
+            "Distribution round has closed"
+        );
+        let claimer_id = env::predecessor_account_id();
+        self.assert_not_blocked(&claimer_id);
+        let custodian_id = env::current_account_id();
+        // ownership is looked up as of the end of the previous block, as a token minted in the
+        // block of the round after it was created was not counted in its supply
+        let snapshot_height = round.block_height.0.checked_sub(1);
+        for token_id in &token_ids {
+            require!(
+                self.tokens.owner_by_id.get(token_id).as_ref() == Some(&claimer_id),
+                "Predecessor must own the claiming tokens"
+            );
+            let owner_at_round = snapshot_height
+                .and_then(|height| self.nft_owner_at(token_id.clone(), U64(height)))
+                .unwrap_or_else(|| {
+                    env::panic_str(&format!(
+                        "Token {} did not exist when the round was created",
+                        token_id
+                    ))
+                });
+            require!(
+                owner_at_round != custodian_id,
+                format!(
+                    "Token {} was held by the contract when the round was created",
+                    token_id
+                )
+            );
+            require!(
+                self.distribution_claims
+                    .insert(&(round_id, token_id.clone())),
+                format!("Token {} has already claimed", token_id)
+            );
+        }
+        let amount = round.per_token.0 * token_ids.len() as Balance;
+        require!(
+            round.claimed.0 + amount <= round.amount.0,
+            "Distribution round is exhausted"
+        );
+        round.claimed = (round.claimed.0 + amount).into();
+        self.distributions.insert(&round_id, &round);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+
+        match round.asset {
+            DistributionAsset::Near => {
+                Promise::new(claimer_id).transfer(amount);
+            }
+            DistributionAsset::Ft(ft_contract_id) => {
+                ext_fungible_token::ft_transfer(
+                    claimer_id,
+                    amount.into(),
+                    Some(format!("Distribution round {}", round_id)),
+                    ft_contract_id,
+                    1,
+                    GAS_FOR_FT_TRANSFER,
+                )
+                .then(ext_self::resolve_distribution_claim(
+                    round_id,
+                    token_ids,
+                    env::current_account_id(),
+                    0,
+                    GAS_FOR_RESOLVE_CLAIM,
+                ));
+            }
+        }
+        amount.into()
+    }
+
+    /// Send what is left of a distribution round after its claim deadline, including the
+    /// shares of burned tokens and the rounding remainder, to the predecessor.
+    #[payable]
+    pub fn sweep_distribution(&mut self, round_id: u64) -> U128 {
+        assert_one_yocto();
+        self.assert_role(Role::Treasurer);
+        let mut round = self
+            .distributions
+            .get(&round_id)
+            .unwrap_or_else(|| env::panic_str("Distribution round not found"));
+        require!(
+            env::block_timestamp() >= round.claim_deadline.0,
+            "Distribution round is still open for claims"
+        );
+        let amount = round.amount.0 - round.claimed.0;
+        require!(amount > 0, "Nothing left to sweep");
+        round.claimed = round.amount;
+        self.distributions.insert(&round_id, &round);
+
+        let treasurer_id = env::predecessor_account_id();
+        match round.asset {
+            DistributionAsset::Near => {
+                Promise::new(treasurer_id).transfer(amount);
+            }
+            DistributionAsset::Ft(ft_contract_id) => {
+                ext_fungible_token::ft_transfer(
+                    treasurer_id,
+                    amount.into(),
+                    Some(format!("Sweep of distribution round {}", round_id)),
+                    ft_contract_id,
+                    1,
+                    GAS_FOR_FT_TRANSFER,
+                )
+                .then(ext_self::resolve_distribution_sweep(
+                    round_id,
+                    amount.into(),
+                    env::current_account_id(),
+                    0,
+                    GAS_FOR_RESOLVE_CLAIM,
+                ));
+            }
+        }
+        amount.into()
+    }
+
+    /// Leaves the swept amount in the round if the fungible token transfer failed.
+    #[private]
+    pub fn resolve_distribution_sweep(&mut self, round_id: u64, amount: U128) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return;
+        }
+        let mut round = self.distributions.get(&round_id).unwrap();
+        round.claimed = (round.claimed.0 - amount.0).into();
+        self.distributions.insert(&round_id, &round);
+    }
+
+    /// Accept transfers of the fungible token of `ft_contract_id` as distribution rounds.
+    #[payable]
+    pub fn add_distribution_ft(&mut self, ft_contract_id: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Treasurer);
+        self.distribution_fts.insert(&ft_contract_id);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    pub fn remove_distribution_ft(&mut self, ft_contract_id: AccountId) {
+        self.assert_role(Role::Treasurer);
+        self.distribution_fts.remove(&ft_contract_id);
+    }
+
+    pub fn is_distribution_ft(&self, ft_contract_id: AccountId) -> bool {
+        self.distribution_fts.contains(&ft_contract_id)
+    }
+
+    /// Lets the tokens claim again if the fungible token transfer of their claim failed.
+    #[private]
+    pub fn resolve_distribution_claim(&mut self, round_id: u64, token_ids: Vec<TokenId>) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return;
+        }
+        let mut round = self.distributions.get(&round_id).unwrap();
+        for token_id in &token_ids {
+            self.distribution_claims
+                .remove(&(round_id, token_id.clone()));
+        }
+        round.claimed = (round.claimed.0 - round.per_token.0 * token_ids.len() as Balance).into();
+        self.distributions.insert(&round_id, &round);
+    }
+
+    pub fn get_distribution(&self, round_id: u64) -> Option<Distribution> {
+        self.distributions.get(&round_id)
+    }
+
+    pub fn is_distribution_claimed(&self, round_id: u64, token_id: TokenId) -> bool {
+        self.distribution_claims.contains(&(round_id, token_id))
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /// Opens a distribution round of the fungible tokens transferred by a treasurer. Only the
+    /// fungible tokens accepted with `add_distribution_ft` can be distributed.
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let _ = msg;
+        self.assert_account_role(Role::Treasurer, &sender_id);
+        let ft_contract_id = env::predecessor_account_id();
+        require!(
+            self.distribution_fts.contains(&ft_contract_id),
+            "Fungible token is not accepted for distributions"
+        );
+        self.internal_create_distribution(DistributionAsset::Ft(ft_contract_id), amount.0);
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::DistributionAsset;
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const CLAIM_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+
+    fn setup() -> (VMContextBuilder, Contract, Vec<TokenId>) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .block_index(1)
+            .build());
+        let token_ids = vec![
+            contract.nft_mint_default(accounts(1)).token_id,
+            contract.nft_mint_default(accounts(2)).token_id,
+        ];
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1_000)
+            .block_index(2)
+            .build());
+        (context, contract, token_ids)
+    }
+
+    #[test]
+    fn test_claim_near_share() {
+        let (mut context, mut contract, token_ids) = setup();
+        let round_id = contract.create_distribution();
+        assert_eq!(
+            contract.get_distribution(round_id).unwrap().per_token,
+            U128(500)
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(CLAIM_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .block_index(3)
+            .build());
+        assert_eq!(
+            contract.claim(round_id, vec![token_ids[0].clone()]),
+            U128(500)
+        );
+        assert!(contract.is_distribution_claimed(round_id, token_ids[0].clone()));
+    }
+
+    #[test]
+    #[should_panic(expected = "has already claimed")]
+    fn test_transferred_token_cannot_claim_twice() {
+        let (mut context, mut contract, token_ids) = setup();
+        let round_id = contract.create_distribution();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(CLAIM_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .block_index(3)
+            .build());
+        contract.claim(round_id, vec![token_ids[0].clone()]);
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .build());
+        contract.nft_transfer(accounts(2), token_ids[0].clone(), None, None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(CLAIM_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.claim(round_id, vec![token_ids[0].clone()]);
+    }
+
+    #[test]
+    fn test_custody_tokens_excluded_and_rest_swept() {
+        let (mut context, mut contract, token_ids) = setup();
+        // a token held by the contract, such as a fractionalized one, cannot claim
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        contract.nft_mint_default(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1_001)
+            .build());
+        let round_id = contract.create_distribution();
+        assert_eq!(
+            contract.get_distribution(round_id).unwrap().per_token,
+            U128(500)
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(CLAIM_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .block_index(3)
+            .build());
+        contract.claim(round_id, vec![token_ids[0].clone()]);
+
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(super::CLAIM_PERIOD)
+            .build());
+        assert_eq!(contract.sweep_distribution(round_id), U128(501));
+    }
+
+    #[test]
+    #[should_panic(expected = "did not exist when the round was created")]
+    fn test_token_minted_in_round_block_cannot_claim() {
+        let (mut context, mut contract, _) = setup();
+        let round_id = contract.create_distribution();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let token_id = contract.nft_mint_default(accounts(3)).token_id;
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(CLAIM_DEPOSIT)
+            .predecessor_account_id(accounts(3))
+            .block_index(3)
+            .build());
+        contract.claim(round_id, vec![token_id]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Fungible token is not accepted for distributions")]
+    fn test_ft_distribution_of_unknown_token() {
+        let (mut context, mut contract, _) = setup();
+        testing_env!(context
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(4))
+            .build());
+        contract.ft_on_transfer(accounts(0), U128(100), String::new());
+    }
+
+    #[test]
+    fn test_ft_distribution_from_owner() {
+        let (mut context, mut contract, _) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        contract.add_distribution_ft(accounts(3));
+        testing_env!(context
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(3))
+            .build());
+        contract.ft_on_transfer(accounts(0), U128(100), String::new());
+        let round = contract.get_distribution(0).unwrap();
+        assert_eq!(round.asset, DistributionAsset::Ft(accounts(3)));
+        assert_eq!(round.per_token, U128(50));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::*;
+use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
+use near_sdk::json_types::U128;
+use near_sdk::require;
+
+#[near_bindgen]
+impl NonFungibleTokenEnumeration for Contract {
+    fn nft_total_supply(&self) -> U128 {
+        self.tokens.nft_total_supply()
+    }
+
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
+        require!(
+            (self.tokens.owner_by_id.len() as u128) > start_index,
+            "Out of bounds, please use a smaller from_index."
+        );
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        self.tokens
+            .owner_by_id
+            .iter()
+            .skip(start_index as usize)
+            .take(limit)
+            .map(|(token_id, owner_id)| self.internal_view_token(token_id, owner_id))
+            .collect()
+    }
+
+    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128 {
+        self.tokens.nft_supply_for_owner(account_id)
+    }
+
+    fn nft_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Token> {
+        let token_set = if let Some(token_set) = self
+            .tokens
+            .tokens_per_owner
+            .as_ref()
+            .and_then(|tokens_per_owner| tokens_per_owner.get(&account_id))
+        {
+            token_set
+        } else {
+            return vec![];
+        };
+        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
+        require!(
+            token_set.len() as u128 > start_index,
+            "Out of bounds, please use a smaller from_index."
+        );
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        require!(limit != 0, "Cannot provide limit of 0.");
+        token_set
+            .iter()
+            .skip(start_index as usize)
+            .take(limit)
+            .map(|token_id| self.internal_view_token(token_id, account_id.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,318 @@
+//! Tokens can be equipped onto warriors of this contract. An equipped token is held in the
+//! custody of the contract account, which is its owner for the NEP-171 views and enumeration,
+//! so it goes along with the warrior when the warrior changes hands and cannot be transferred,
+//! approved or rented on its own. Unequipping returns it to the owner of the warrior. Both
+//! moves go through the same checks as a transfer of the equipped token.
+use crate::*;
+use near_sdk::assert_one_yocto;
+
+impl Contract {
+    fn internal_unequip(&mut self, parent_token_id: &TokenId, child_token_id: &TokenId) {
+        let mut children = self.equipment.get(parent_token_id).unwrap_or_default();
+        children.retain(|token_id| token_id != child_token_id);
+        if children.is_empty() {
+            self.equipment.remove(parent_token_id);
+        } else {
+            self.equipment.insert(parent_token_id, &children);
+        }
+        self.equipped_in.remove(child_token_id);
+    }
+
+    /// Takes `token_id` off its warrior if it is equipped, and returns everything equipped on
+    /// it to `owner_id`. Used when `token_id` is burned.
+    pub(crate) fn internal_release_equipment(&mut self, token_id: &TokenId, owner_id: &AccountId) {
+        if let Some(parent_token_id) = self.equipped_in.get(token_id) {
+            self.internal_unequip(&parent_token_id, token_id);
+        }
+        let custodian_id = env::current_account_id();
+        for child_token_id in self.equipment.remove(token_id).unwrap_or_default() {
+            self.equipped_in.remove(&child_token_id);
+            self.internal_move_token(&child_token_id, &custodian_id, owner_id, "unequip");
+        }
+    }
+
+    /// Panics unless `token_id` can move from `sender_id` to `receiver_id` as it is equipped
+    /// or unequipped: the contract must not be paused, the transfer policy must allow it, the
+    /// token must be active and not locked by a check-in, and neither account blocked.
+    fn assert_equipment_move_allowed(
+        &self,
+        token_id: &TokenId,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+    ) {
+        self.assert_not_paused();
+        self.assert_transfer_allowed(token_id, receiver_id);
+        self.assert_active(token_id);
+        self.assert_not_checked_in(token_id);
+        self.assert_not_blocked(sender_id);
+        self.assert_not_blocked(receiver_id);
+    }
+
+    pub(crate) fn assert_not_equipped(&self, token_id: &TokenId) {
+        require!(
+            self.equipped_in.get(token_id).is_none(),
+            "Equipped tokens cannot be transferred, unequip them first"
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Equip `child_token_id` onto `parent_token_id`, both owned by the predecessor, moving the
+    /// child into the custody of the contract. Approvals and rentals of the child are cleared.
+    #[payable]
+    pub fn equip(&mut self, parent_token_id: TokenId, child_token_id: TokenId) {
+        let initial_storage_usage = env::storage_usage();
+        require!(
+            parent_token_id != child_token_id,
+            "A token cannot be equipped onto itself"
+        );
+        let owner_id = env::predecessor_account_id();
+        for token_id in &[&parent_token_id, &child_token_id] {
+            require!(
+                self.tokens.owner_by_id.get(token_id).as_ref() == Some(&owner_id),
+                "Predecessor must own both tokens"
+            );
+        }
+        require!(
+            self.equipment.get(&child_token_id).is_none(),
+            "A token with equipment cannot be equipped"
+        );
+        self.assert_not_staked(&child_token_id);
+        let custodian_id = env::current_account_id();
+        self.assert_equipment_move_allowed(&child_token_id, &owner_id, &custodian_id);
+
+        if let Some(by_id) = self.tokens.approvals_by_id.as_mut() {
+            by_id.remove(&child_token_id);
+        }
+        self.approval_expirations.remove(&child_token_id);
+        self.internal_clear_rental(&child_token_id);
+        self.internal_move_token(&child_token_id, &owner_id, &custodian_id, "equip");
+
+        let mut children = self.equipment.get(&parent_token_id).unwrap_or_default();
+        children.push(child_token_id.clone());
+        self.equipment.insert(&parent_token_id, &children);
+        self.equipped_in.insert(&child_token_id, &parent_token_id);
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Take `child_token_id` off `parent_token_id` and return it to the owner of the parent.
+    #[payable]
+    pub fn unequip(&mut self, parent_token_id: TokenId, child_token_id: TokenId) {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        require!(
+            self.tokens.owner_by_id.get(&parent_token_id).as_ref() == Some(&owner_id),
+            "Predecessor must own the parent token"
+        );
+        require!(
+            self.equipped_in.get(&child_token_id).as_ref() == Some(&parent_token_id),
+            "Token is not equipped onto the parent token"
+        );
+        let custodian_id = env::current_account_id();
+        self.assert_equipment_move_allowed(&child_token_id, &custodian_id, &owner_id);
+        self.internal_unequip(&parent_token_id, &child_token_id);
+        self.internal_move_token(&child_token_id, &custodian_id, &owner_id, "unequip");
+    }
+
+    /// Returns the tokens equipped onto `token_id`.
+    pub fn nft_equipment(&self, token_id: TokenId) -> Vec<Token> {
+        let custodian_id = env::current_account_id();
+        self.equipment
+            .get(&token_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child_token_id| self.internal_view_token(child_token_id, custodian_id.clone()))
+            .collect()
+    }
+
+    /// Returns the token `token_id` is equipped onto, if any.
+    pub fn nft_equipped_in(&self, token_id: TokenId) -> Option<TokenId> {
+        self.equipped_in.get(&token_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::transfer_policy::TransferPolicy;
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const EQUIP_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+
+    fn setup_unequipped() -> (VMContextBuilder, Contract, TokenId, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let warrior = contract.nft_mint_default(accounts(1)).token_id;
+        let item = contract.nft_mint_default(accounts(1)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(EQUIP_DEPOSIT)
+            .build());
+        (context, contract, warrior, item)
+    }
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId, TokenId) {
+        let (context, mut contract, warrior, item) = setup_unequipped();
+        contract.equip(warrior.clone(), item.clone());
+        (context, contract, warrior, item)
+    }
+
+    /// Transfers the warrior of `setup` to `accounts(2)`, who then owns the equipped item.
+    fn transfer_warrior(context: &mut VMContextBuilder, contract: &mut Contract, warrior: TokenId) {
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(2), warrior, None, None);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+    }
+
+    #[test]
+    fn test_equipment_follows_warrior() {
+        let (mut context, mut contract, warrior, item) = setup();
+        assert_eq!(
+            contract.nft_equipped_in(item.clone()),
+            Some(warrior.clone())
+        );
+        assert_eq!(contract.nft_equipment(warrior.clone())[0].token_id, item);
+        assert_eq!(
+            contract.nft_token(item.clone()).unwrap().owner_id,
+            accounts(0)
+        );
+        assert_eq!(contract.nft_supply_for_owner(accounts(1)), U128(1));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .build());
+        contract.nft_transfer(accounts(2), warrior.clone(), None, None);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.unequip(warrior.clone(), item.clone());
+        assert_eq!(
+            contract.nft_token(item.clone()).unwrap().owner_id,
+            accounts(2)
+        );
+        assert_eq!(contract.nft_equipped_in(item), None);
+        assert!(contract.nft_equipment(warrior).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Equipped tokens cannot be transferred")]
+    fn test_transfer_equipped_item() {
+        let (mut context, mut contract, _, item) = setup();
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.nft_transfer(accounts(2), item, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Predecessor must own the parent token")]
+    fn test_unequip_not_owner() {
+        let (mut context, mut contract, warrior, item) = setup();
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.unequip(warrior, item);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn test_equip_while_paused() {
+        let (mut context, mut contract, warrior, item) = setup_unequipped();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.pause();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.equip(warrior, item);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is still in its holding period")]
+    fn test_equip_in_holding_period() {
+        let (mut context, mut contract, warrior, item) = setup_unequipped();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_transfer_policy(TransferPolicy {
+            min_holding_period: U64(1_000),
+            ..Default::default()
+        });
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.equip(warrior, item);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token has expired")]
+    fn test_equip_expired_item() {
+        let (mut context, mut contract, warrior, _) = setup_unequipped();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut metadata = sample_token_metadata();
+        metadata.expires_at = Some("1".to_string());
+        contract.nft_mint("expiring".to_string(), accounts(1), metadata);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(1_000_000)
+            .build());
+        contract.equip(warrior, "expiring".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "is blocked")]
+    fn test_equip_blocked_owner() {
+        let (mut context, mut contract, warrior, item) = setup_unequipped();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.add_to_blocklist(accounts(1));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.equip(warrior, item);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token transfers are locked")]
+    fn test_unequip_locked_item() {
+        let (mut context, mut contract, warrior, item) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.lock_transfers(item.clone(), None);
+        transfer_warrior(&mut context, &mut contract, warrior.clone());
+        contract.unequip(warrior, item);
+    }
+
+    #[test]
+    #[should_panic(expected = "is blocked")]
+    fn test_unequip_to_blocked_owner() {
+        let (mut context, mut contract, warrior, item) = setup();
+        transfer_warrior(&mut context, &mut contract, warrior.clone());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(EQUIP_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.add_to_blocklist(accounts(2));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.unequip(warrior, item);
+    }
+}
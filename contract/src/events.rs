@@ -0,0 +1,55 @@
+//! NEP-297 events for the standards the contract implements itself, and for its own
+//! extensions. The NEP-171 and NEP-141 events come from near-contract-standards.
+use near_sdk::env;
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+
+const CONTRACT_STANDARD: &str = "hellomint";
+const CONTRACT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a, T: Serialize> {
+    standard: &'a str,
+    version: &'a str,
+    event: &'a str,
+    data: T,
+}
+
+/// Logs `data` as the `event` event of `standard`, in the `EVENT_JSON:` format of NEP-297.
+pub(crate) fn emit_event<T: Serialize>(standard: &str, version: &str, event: &str, data: T) {
+    let log = EventLog {
+        standard,
+        version,
+        event,
+        data,
+    };
+    env::log_str(&format!(
+        "EVENT_JSON:{}",
+        serde_json::to_string(&log).unwrap()
+    ));
+}
+
+/// Logs `data` as the `event` event of the contract's own extensions.
+pub(crate) fn emit_contract_event<T: Serialize>(event: &str, data: T) {
+    emit_event(CONTRACT_STANDARD, CONTRACT_VERSION, event, [data]);
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::emit_event;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_emit_event() {
+        testing_env!(VMContextBuilder::new().build());
+        emit_event("nep245", "1.0.0", "mt_mint", [1, 2]);
+        assert_eq!(
+            get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"nep245","version":"1.0.0","event":"mt_mint","data":[1,2]}"#
+            ]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! NFTs of NEP-171 contracts accepted by the admins can be attached to warriors by sending them
+//! to this contract with `nft_transfer_call` and a `msg` naming the warrior. They are held in
+//! the custody of the contract, go along with the warrior when it changes hands, and are sent
+//! back out with `detach_external`, or to the owner when the warrior is burned. NFTs that fail
+//! to be sent after a burn can be claimed again with `claim_external`.
+use crate::roles::Role;
+use crate::*;
+use near_contract_standards::non_fungible_token::core::NonFungibleTokenReceiver;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json;
+use near_sdk::{assert_one_yocto, ext_contract, Gas, PromiseResult};
+
+const GAS_FOR_NFT_TRANSFER: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_RESOLVE_DETACH: Gas = Gas(10_000_000_000_000);
+// bounds the storage of attached tokens, which the contract pays for
+const MAX_EXTERNAL_EQUIPMENT: usize = 10;
+
+#[ext_contract(ext_nft)]
+trait ExternalNft {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
+}
+
+#[ext_contract(ext_self)]
+trait DetachResolver {
+    fn resolve_detach_external(
+        &mut self,
+        parent_token_id: Option<TokenId>,
+        owner_id: AccountId,
+        contract_id: AccountId,
+        token_id: TokenId,
+    );
+}
+
+/// An NFT of another contract attached to a warrior.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExternalToken {
+    pub contract_id: AccountId,
+    pub token_id: TokenId,
+}
+
+/// The `msg` of the `nft_transfer_call` attaching an external NFT.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct AttachMsg {
+    parent_token_id: TokenId,
+}
+
+impl Contract {
+    /// Sends `token` to `owner_id`. If that fails, the token is attached back to
+    /// `parent_token_id`, or kept for `owner_id` to claim if there is no parent anymore.
+    fn send_external(
+        &self,
+        token: ExternalToken,
+        parent_token_id: Option<TokenId>,
+        owner_id: AccountId,
+    ) -> Promise {
+        ext_nft::nft_transfer(
+            owner_id.clone(),
+            token.token_id.clone(),
+            None,
+            None,
+            token.contract_id.clone(),
+            1,
+            GAS_FOR_NFT_TRANSFER,
+        )
+        .then(ext_self::resolve_detach_external(
+            parent_token_id,
+            owner_id,
+            token.contract_id,
+            token.token_id,
+            env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_DETACH,
+        ))
+    }
+
+    /// Sends every external NFT attached to `token_id` to `owner_id`. Used when `token_id` is
+    /// burned.
+    pub(crate) fn internal_release_external_equipment(
+        &mut self,
+        token_id: &TokenId,
+        owner_id: &AccountId,
+    ) {
+        for token in self.external_equipment.remove(token_id).unwrap_or_default() {
+            self.send_external(token, Some(token_id.clone()), owner_id.clone());
+        }
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenReceiver for Contract {
+    /// Attach the NFT sent by the calling contract, which must be an accepted equipment
+    /// contract, to the warrior named in `msg`, as
+    /// `{"parent_token_id": "<token id>"}`. The warrior must belong to the previous owner of
+    /// the NFT, otherwise the call fails and the NFT is returned.
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let _ = sender_id;
+        let contract_id = env::predecessor_account_id();
+        require!(
+            contract_id != env::current_account_id(),
+            "Use equip for tokens of this contract"
+        );
+        require!(
+            self.equipment_contracts.contains(&contract_id),
+            "NFT contract is not accepted as equipment"
+        );
+        let AttachMsg { parent_token_id } = serde_json::from_str(&msg)
+            .unwrap_or_else(|_| env::panic_str("msg must name a parent_token_id"));
+        require!(
+            self.tokens.owner_by_id.get(&parent_token_id) == Some(previous_owner_id),
+            "Previous owner must own the parent token"
+        );
+        let mut children = self
+            .external_equipment
+            .get(&parent_token_id)
+            .unwrap_or_default();
+        require!(
+            children.len() < MAX_EXTERNAL_EQUIPMENT,
+            "Parent token cannot hold more external tokens"
+        );
+        children.push(ExternalToken {
+            contract_id,
+            token_id,
+        });
+        self.external_equipment.insert(&parent_token_id, &children);
+        PromiseOrValue::Value(false)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Accept the NFTs of `contract_id` as external equipment.
+    #[payable]
+    pub fn add_equipment_contract(&mut self, contract_id: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Admin);
+        self.equipment_contracts.insert(&contract_id);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Stop accepting the NFTs of `contract_id`. Those already attached stay attached.
+    pub fn remove_equipment_contract(&mut self, contract_id: AccountId) {
+        self.assert_role(Role::Admin);
+        self.equipment_contracts.remove(&contract_id);
+    }
+
+    pub fn is_equipment_contract(&self, contract_id: AccountId) -> bool {
+        self.equipment_contracts.contains(&contract_id)
+    }
+
+    /// Send an external NFT attached to `parent_token_id` back to the owner of the parent.
+    #[payable]
+    pub fn detach_external(
+        &mut self,
+        parent_token_id: TokenId,
+        contract_id: AccountId,
+        token_id: TokenId,
+    ) -> Promise {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        require!(
+            self.tokens.owner_by_id.get(&parent_token_id).as_ref() == Some(&owner_id),
+            "Predecessor must own the parent token"
+        );
+        let token = ExternalToken {
+            contract_id,
+            token_id,
+        };
+        let mut children = self
+            .external_equipment
+            .get(&parent_token_id)
+            .unwrap_or_default();
+        let index = children
+            .iter()
+            .position(|child| child == &token)
+            .unwrap_or_else(|| env::panic_str("Token is not attached to the parent token"));
+        children.remove(index);
+        if children.is_empty() {
+            self.external_equipment.remove(&parent_token_id);
+        } else {
+            self.external_equipment.insert(&parent_token_id, &children);
+        }
+
+        self.send_external(token, Some(parent_token_id), owner_id)
+    }
+
+    /// Send an external NFT released to the predecessor by a burn, which could not be sent
+    /// at the time.
+    #[payable]
+    pub fn claim_external(&mut self, contract_id: AccountId, token_id: TokenId) -> Promise {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let token = ExternalToken {
+            contract_id,
+            token_id,
+        };
+        let mut unclaimed = self.unclaimed_external.get(&owner_id).unwrap_or_default();
+        let index = unclaimed
+            .iter()
+            .position(|unclaimed| unclaimed == &token)
+            .unwrap_or_else(|| env::panic_str("Token is not released to the predecessor"));
+        unclaimed.remove(index);
+        if unclaimed.is_empty() {
+            self.unclaimed_external.remove(&owner_id);
+        } else {
+            self.unclaimed_external.insert(&owner_id, &unclaimed);
+        }
+        self.send_external(token, None, owner_id)
+    }
+
+    /// Attaches the token back to its parent if sending it out failed, or keeps it for
+    /// `owner_id` to claim if the parent was burned.
+    #[private]
+    pub fn resolve_detach_external(
+        &mut self,
+        parent_token_id: Option<TokenId>,
+        owner_id: AccountId,
+        contract_id: AccountId,
+        token_id: TokenId,
+    ) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return;
+        }
+        let token = ExternalToken {
+            contract_id,
+            token_id,
+        };
+        match parent_token_id.filter(|parent| self.tokens.owner_by_id.get(parent).is_some()) {
+            Some(parent_token_id) => {
+                let mut children = self
+                    .external_equipment
+                    .get(&parent_token_id)
+                    .unwrap_or_default();
+                children.push(token);
+                self.external_equipment.insert(&parent_token_id, &children);
+            }
+            None => {
+                let mut unclaimed = self.unclaimed_external.get(&owner_id).unwrap_or_default();
+                unclaimed.push(token);
+                self.unclaimed_external.insert(&owner_id, &unclaimed);
+            }
+        }
+    }
+
+    /// Returns the external NFTs released to `account_id` that it can claim.
+    pub fn get_unclaimed_external(&self, account_id: AccountId) -> Vec<ExternalToken> {
+        self.unclaimed_external.get(&account_id).unwrap_or_default()
+    }
+
+    /// Returns the external NFTs attached to `token_id`.
+    pub fn nft_external_equipment(&self, token_id: TokenId) -> Vec<ExternalToken> {
+        self.external_equipment.get(&token_id).unwrap_or_default()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::ExternalToken;
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenReceiver;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let warrior = contract.nft_mint_default(accounts(1)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.add_equipment_contract(accounts(3));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(3))
+            .build());
+        (context, contract, warrior)
+    }
+
+    fn attach_msg(parent_token_id: &str) -> String {
+        format!("{{\"parent_token_id\": \"{}\"}}", parent_token_id)
+    }
+
+    #[test]
+    fn test_attach_and_detach_external() {
+        let (mut context, mut contract, warrior) = setup();
+        contract.nft_on_transfer(
+            accounts(1),
+            accounts(1),
+            "sword".to_string(),
+            attach_msg(&warrior),
+        );
+        let sword = ExternalToken {
+            contract_id: accounts(3),
+            token_id: "sword".to_string(),
+        };
+        assert_eq!(
+            contract.nft_external_equipment(warrior.clone()),
+            vec![sword.clone()]
+        );
+
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.detach_external(warrior.clone(), sword.contract_id, sword.token_id);
+        assert!(contract.nft_external_equipment(warrior).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "NFT contract is not accepted as equipment")]
+    fn test_attach_from_unknown_contract() {
+        let (mut context, mut contract, warrior) = setup();
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.nft_on_transfer(
+            accounts(1),
+            accounts(1),
+            "sword".to_string(),
+            attach_msg(&warrior),
+        );
+    }
+
+    #[test]
+    fn test_failed_release_after_burn() {
+        let (context, mut contract, _) = setup();
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.resolve_detach_external(
+            Some("burned".to_string()),
+            accounts(1),
+            accounts(3),
+            "sword".to_string(),
+        );
+        assert_eq!(
+            contract.get_unclaimed_external(accounts(1)),
+            vec![ExternalToken {
+                contract_id: accounts(3),
+                token_id: "sword".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Previous owner must own the parent token")]
+    fn test_attach_to_foreign_warrior() {
+        let (_, mut contract, warrior) = setup();
+        contract.nft_on_transfer(
+            accounts(2),
+            accounts(2),
+            "sword".to_string(),
+            attach_msg(&warrior),
+        );
+    }
+}
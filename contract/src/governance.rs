@@ -0,0 +1,324 @@
+use crate::roles::Role;
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+
+const MAX_OPTIONS: usize = 10;
+
+/// A change to the contract the owner can bind to an option of a proposal, applied when the
+/// option wins.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ProposalAction {
+    SetMintPrice(U128),
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub proposer_id: AccountId,
+    pub description: String,
+    pub options: Vec<String>,
+    /// Voting is open from `starts_at` until `ends_at`, as block timestamps.
+    pub starts_at: U64,
+    pub ends_at: U64,
+    /// Block the proposal was created at. Tokens vote with their owner as of the end of the
+    /// block before, so tokens minted or bought later cannot vote.
+    pub block_height: U64,
+    /// Number of votes cast for each option, one per token.
+    pub tallies: Vec<u64>,
+    /// Option index -> action applied if the option wins.
+    pub actions: Vec<(u32, ProposalAction)>,
+    pub executed: bool,
+}
+
+impl Proposal {
+    /// The option with the most votes, if any option has strictly more than the others.
+    pub fn winning_option(&self) -> Option<u32> {
+        let max = *self.tallies.iter().max()?;
+        let mut winners = self.tallies.iter().enumerate().filter(|(_, &n)| n == max);
+        match (winners.next(), winners.next()) {
+            (Some((option, _)), None) if max > 0 => Some(option as u32),
+            _ => None,
+        }
+    }
+}
+
+impl Contract {
+    fn expect_proposal(&self, proposal_id: u64) -> Proposal {
+        self.proposals
+            .get(&proposal_id)
+            .unwrap_or_else(|| env::panic_str("Proposal not found"))
+    }
+
+    fn apply_proposal_action(&mut self, action: &ProposalAction) {
+        match action {
+            ProposalAction::SetMintPrice(price) => self.mint_price = price.0,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Open a proposal to the holders, voted on from `starts_at` until `ends_at` (block
+    /// timestamps in nanoseconds). Only holders can create proposals, and the attached deposit
+    /// must cover its storage.
+    #[payable]
+    pub fn create_proposal(
+        &mut self,
+        description: String,
+        options: Vec<String>,
+        starts_at: U64,
+        ends_at: U64,
+    ) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        let proposer_id = env::predecessor_account_id();
+        require!(
+            self.tokens
+                .tokens_per_owner
+                .as_ref()
+                .and_then(|by_owner| by_owner.get(&proposer_id))
+                .is_some(),
+            "Only holders can create proposals"
+        );
+        require!(
+            options.len() >= 2 && options.len() <= MAX_OPTIONS,
+            format!("A proposal needs between 2 and {} options", MAX_OPTIONS)
+        );
+        require!(
+            starts_at.0 < ends_at.0 && ends_at.0 > env::block_timestamp(),
+            "Voting window must end in the future"
+        );
+
+        let proposal_id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        self.proposals.insert(
+            &proposal_id,
+            &Proposal {
+                proposer_id,
+                description,
+                tallies: vec![0; options.len()],
+                options,
+                starts_at,
+                ends_at,
+                block_height: env::block_height().into(),
+                actions: vec![],
+                executed: false,
+            },
+        );
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        proposal_id
+    }
+
+    /// Bind `action` to `option` of a proposal, to be applied when the option wins. Only
+    /// possible before voting starts, so that voters know what they vote for.
+    pub fn bind_proposal_action(&mut self, proposal_id: u64, option: u32, action: ProposalAction) {
+        self.assert_role(Role::Admin);
+        let mut proposal = self.expect_proposal(proposal_id);
+        require!(
+            env::block_timestamp() < proposal.starts_at.0,
+            "Voting has already started"
+        );
+        require!((option as usize) < proposal.options.len(), "Invalid option");
+        proposal.actions.retain(|(bound, _)| *bound != option);
+        proposal.actions.push((option, action));
+        self.proposals.insert(&proposal_id, &proposal);
+    }
+
+    /// Cast one vote for `option` with each of `token_ids`, which the predecessor must have
+    /// owned when the proposal was created. A token votes at most once on a proposal.
+    #[payable]
+    pub fn vote(&mut self, proposal_id: u64, token_ids: Vec<TokenId>, option: u32) {
+        let initial_storage_usage = env::storage_usage();
+        let mut proposal = self.expect_proposal(proposal_id);
+        let now = env::block_timestamp();
+        require!(
+            proposal.starts_at.0 <= now && now < proposal.ends_at.0,
+            "Voting is not open"
+        );
+        require!((option as usize) < proposal.options.len(), "Invalid option");
+        let voter_id = env::predecessor_account_id();
+        self.assert_not_blocked(&voter_id);
+        let snapshot_height = proposal.block_height.0.checked_sub(1);
+        for token_id in &token_ids {
+            let owner_at_proposal =
+                snapshot_height.and_then(|height| self.nft_owner_at(token_id.clone(), U64(height)));
+            require!(
+                owner_at_proposal.as_ref() == Some(&voter_id),
+                "Predecessor must have owned the voting tokens when the proposal was created"
+            );
+            let key = (proposal_id, token_id.clone());
+            require!(
+                self.token_votes.get(&key).is_none(),
+                format!("Token {} has already voted", token_id)
+            );
+            self.token_votes.insert(&key, &option);
+        }
+        proposal.tallies[option as usize] += token_ids.len() as u64;
+        self.proposals.insert(&proposal_id, &proposal);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Close a proposal once voting has ended, applying the action bound to the winning
+    /// option, if any. Returns the winning option, or `None` on a tie or without votes.
+    pub fn execute_proposal(&mut self, proposal_id: u64) -> Option<u32> {
+        let mut proposal = self.expect_proposal(proposal_id);
+        require!(
+            env::block_timestamp() >= proposal.ends_at.0,
+            "Voting has not ended yet"
+        );
+        require!(!proposal.executed, "Proposal already executed");
+        proposal.executed = true;
+        let winning_option = proposal.winning_option();
+        if let Some(option) = winning_option {
+            if let Some((_, action)) = proposal.actions.iter().find(|(bound, _)| *bound == option) {
+                self.apply_proposal_action(action);
+            }
+        }
+        self.proposals.insert(&proposal_id, &proposal);
+        winning_option
+    }
+
+    pub fn get_proposal(&self, proposal_id: u64) -> Option<Proposal> {
+        self.proposals.get(&proposal_id)
+    }
+
+    /// Returns the proposals with ids from `from_index`, oldest first.
+    pub fn get_proposals(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<(u64, Proposal)> {
+        let start = from_index.map_or(0, |index| index.0);
+        let limit = limit.unwrap_or(u64::MAX);
+        (start..self.next_proposal_id)
+            .filter_map(|proposal_id| Some((proposal_id, self.proposals.get(&proposal_id)?)))
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// Returns the option `token_id` voted for on a proposal, if it voted.
+    pub fn get_token_vote(&self, proposal_id: u64, token_id: TokenId) -> Option<u32> {
+        self.token_votes.get(&(proposal_id, token_id))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::ProposalAction;
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const PROPOSAL_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId, u64) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let token_id = contract.nft_mint_default(accounts(1)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(PROPOSAL_DEPOSIT)
+            .block_index(1)
+            .build());
+        let proposal_id = contract.create_proposal(
+            "Raise the mint price?".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
+            U64(100),
+            U64(200),
+        );
+        (context, contract, token_id, proposal_id)
+    }
+
+    #[test]
+    fn test_vote_and_execute_bound_action() {
+        let (mut context, mut contract, token_id, proposal_id) = setup();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.bind_proposal_action(proposal_id, 0, ProposalAction::SetMintPrice(U128(1_000)));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(150)
+            .build());
+        contract.vote(proposal_id, vec![token_id.clone()], 0);
+        assert_eq!(contract.get_token_vote(proposal_id, token_id), Some(0));
+        assert_eq!(
+            contract.get_proposal(proposal_id).unwrap().tallies,
+            vec![1, 0]
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(200)
+            .build());
+        assert_eq!(contract.execute_proposal(proposal_id), Some(0));
+        assert_eq!(contract.get_mint_price(), U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "has already voted")]
+    fn test_transferred_token_cannot_vote_twice() {
+        let (mut context, mut contract, token_id, proposal_id) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(150)
+            .build());
+        contract.vote(proposal_id, vec![token_id.clone()], 0);
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .build());
+        contract.nft_transfer(accounts(2), token_id.clone(), None, None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(PROPOSAL_DEPOSIT)
+            .build());
+        contract.vote(proposal_id, vec![token_id], 1);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Predecessor must have owned the voting tokens when the proposal was created"
+    )]
+    fn test_token_minted_after_proposal_cannot_vote() {
+        let (mut context, mut contract, _, proposal_id) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .build());
+        let token_id = contract.nft_mint_default(accounts(2)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(PROPOSAL_DEPOSIT)
+            .block_index(2)
+            .block_timestamp(150)
+            .build());
+        contract.vote(proposal_id, vec![token_id], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only holders can create proposals")]
+    fn test_non_holder_cannot_propose() {
+        let (mut context, mut contract, _, _) = setup();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.create_proposal(
+            "Lower the mint price?".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
+            U64(100),
+            U64(200),
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! Loans of NEAR against warriors. A borrower posts a loan request, escrowing the collateral
+//! token in the custody of the contract until the loan is either cancelled before being funded,
+//! repaid with interest before its deadline, or defaulted, in which case the lender claims the
+//! token.
+use crate::*;
+use near_sdk::assert_one_yocto;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Balance;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum LoanStatus {
+    /// Waiting for a lender.
+    Open,
+    /// Funded, until repaid or defaulted.
+    Active,
+    Repaid,
+    Defaulted,
+    Cancelled,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Loan {
+    pub token_id: TokenId,
+    pub borrower_id: AccountId,
+    pub principal: U128,
+    pub interest: U128,
+    /// Time the borrower has to repay once the loan is funded, in nanoseconds.
+    pub duration: U64,
+    pub lender_id: Option<AccountId>,
+    /// Block timestamp the loan was funded at.
+    pub started_at: Option<U64>,
+    pub status: LoanStatus,
+}
+
+impl Loan {
+    /// Block timestamp after which an active loan is in default.
+    pub fn deadline(&self) -> Option<u64> {
+        self.started_at
+            .map(|started_at| started_at.0.saturating_add(self.duration.0))
+    }
+}
+
+impl Contract {
+    fn expect_loan(&self, loan_id: u64) -> Loan {
+        self.loans
+            .get(&loan_id)
+            .unwrap_or_else(|| env::panic_str("Loan not found"))
+    }
+
+    /// Ends a loan with `status`, releasing the collateral to `receiver_id`, who must not be
+    /// blocked. Unless the collateral goes back to the borrower, the transfer policy applies,
+    /// and the collateral must be within its validity window and not locked by a check-in.
+    fn internal_close_loan(
+        &mut self,
+        loan_id: u64,
+        mut loan: Loan,
+        status: LoanStatus,
+        receiver_id: &AccountId,
+    ) {
+        self.assert_not_blocked(receiver_id);
+        if receiver_id != &loan.borrower_id {
+            self.assert_transfer_allowed(&loan.token_id, receiver_id);
+            self.assert_active(&loan.token_id);
+            self.assert_not_checked_in(&loan.token_id);
+        }
+        self.internal_move_token(
+            &loan.token_id,
+            &env::current_account_id(),
+            receiver_id,
+            "loan",
+        );
+        loan.status = status;
+        self.loans.insert(&loan_id, &loan);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Ask for a loan of `principal` against `token_id`, owned by the predecessor, to be repaid
+    /// with `interest` within `duration` nanoseconds of being funded. The token is escrowed in
+    /// the contract right away, and its approvals and rental are cleared. Returns the loan id.
+    #[payable]
+    pub fn request_loan(
+        &mut self,
+        token_id: TokenId,
+        principal: U128,
+        interest: U128,
+        duration: U64,
+    ) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        let borrower_id = env::predecessor_account_id();
+        self.assert_not_blocked(&borrower_id);
+        require!(
+            self.tokens.owner_by_id.get(&token_id).as_ref() == Some(&borrower_id),
+            "Predecessor must own the token"
+        );
+        require!(principal.0 > 0, "Principal must be positive");
+        require!(duration.0 > 0, "Duration must be positive");
+        self.assert_not_staked(&token_id);
+        self.assert_transfer_allowed(&token_id, &env::current_account_id());
+        self.assert_active(&token_id);
+        self.assert_not_checked_in(&token_id);
+
+        if let Some(by_id) = self.tokens.approvals_by_id.as_mut() {
+            by_id.remove(&token_id);
+        }
+        self.approval_expirations.remove(&token_id);
+        self.internal_clear_rental(&token_id);
+        self.internal_move_token(&token_id, &borrower_id, &env::current_account_id(), "loan");
+
+        let loan_id = self.next_loan_id;
+        self.next_loan_id += 1;
+        self.loans.insert(
+            &loan_id,
+            &Loan {
+                token_id,
+                borrower_id,
+                principal,
+                interest,
+                duration,
+                lender_id: None,
+                started_at: None,
+                status: LoanStatus::Open,
+            },
+        );
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        loan_id
+    }
+
+    /// Withdraw a loan request nobody has funded yet, returning the token to the borrower.
+    #[payable]
+    pub fn cancel_loan(&mut self, loan_id: u64) {
+        assert_one_yocto();
+        let loan = self.expect_loan(loan_id);
+        require!(
+            loan.borrower_id == env::predecessor_account_id(),
+            "Only the borrower can cancel the loan"
+        );
+        require!(loan.status == LoanStatus::Open, "Loan is not open");
+        let borrower_id = loan.borrower_id.clone();
+        self.internal_close_loan(loan_id, loan, LoanStatus::Cancelled, &borrower_id);
+    }
+
+    /// Fund an open loan. The attached deposit must cover the principal, which is paid out to
+    /// the borrower. The lender must be allowed to receive the token, should the loan default.
+    #[payable]
+    pub fn fund_loan(&mut self, loan_id: u64) {
+        let initial_storage_usage = env::storage_usage();
+        let mut loan = self.expect_loan(loan_id);
+        require!(loan.status == LoanStatus::Open, "Loan is not open");
+        let lender_id = env::predecessor_account_id();
+        self.assert_not_blocked(&lender_id);
+        require!(
+            lender_id != loan.borrower_id,
+            "The borrower cannot fund their own loan"
+        );
+        self.assert_transfer_allowed(&loan.token_id, &lender_id);
+        self.assert_active(&loan.token_id);
+        loan.lender_id = Some(lender_id);
+        loan.started_at = Some(env::block_timestamp().into());
+        loan.status = LoanStatus::Active;
+        self.loans.insert(&loan_id, &loan);
+
+        let principal: Balance = loan.principal.into();
+        Promise::new(loan.borrower_id).transfer(principal);
+        utils::refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            principal,
+        );
+    }
+
+    /// Repay an active loan before its deadline, paying the principal and interest to the
+    /// lender, and get the token back.
+    #[payable]
+    pub fn repay_loan(&mut self, loan_id: u64) {
+        let initial_storage_usage = env::storage_usage();
+        let loan = self.expect_loan(loan_id);
+        require!(
+            loan.borrower_id == env::predecessor_account_id(),
+            "Only the borrower can repay the loan"
+        );
+        require!(loan.status == LoanStatus::Active, "Loan is not active");
+        require!(
+            env::block_timestamp() < loan.deadline().unwrap(),
+            "Loan is in default"
+        );
+        let amount = loan.principal.0 + loan.interest.0;
+        let lender_id = loan.lender_id.clone().unwrap();
+        let borrower_id = loan.borrower_id.clone();
+        self.internal_close_loan(loan_id, loan, LoanStatus::Repaid, &borrower_id);
+
+        Promise::new(lender_id).transfer(amount);
+        utils::refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            amount,
+        );
+    }
+
+    /// Take the token of a loan that was not repaid before its deadline.
+    #[payable]
+    pub fn claim_defaulted_loan(&mut self, loan_id: u64) {
+        assert_one_yocto();
+        let loan = self.expect_loan(loan_id);
+        let lender_id = env::predecessor_account_id();
+        require!(
+            loan.lender_id.as_ref() == Some(&lender_id),
+            "Only the lender can claim the token"
+        );
+        require!(loan.status == LoanStatus::Active, "Loan is not active");
+        require!(
+            env::block_timestamp() >= loan.deadline().unwrap(),
+            "Loan is not in default yet"
+        );
+        self.internal_close_loan(loan_id, loan, LoanStatus::Defaulted, &lender_id);
+    }
+
+    pub fn get_loan(&self, loan_id: u64) -> Option<Loan> {
+        self.loans.get(&loan_id)
+    }
+
+    /// Returns the loans waiting for a lender with ids from `from_index`, oldest first.
+    pub fn get_open_loans(&self, from_index: Option<U64>, limit: Option<u64>) -> Vec<(u64, Loan)> {
+        let start = from_index.map_or(0, |index| index.0);
+        let limit = limit.unwrap_or(u64::MAX);
+        (start..self.next_loan_id)
+            .filter_map(|loan_id| Some((loan_id, self.loans.get(&loan_id)?)))
+            .filter(|(_, loan)| loan.status == LoanStatus::Open)
+            .take(limit as usize)
+            .collect()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::LoanStatus;
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const LOAN_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+    const PRINCIPAL: u128 = 1_000;
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId, u64) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let token_id = contract.nft_mint_default(accounts(1)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(LOAN_DEPOSIT)
+            .build());
+        let loan_id = contract.request_loan(token_id.clone(), U128(PRINCIPAL), U128(100), U64(50));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(PRINCIPAL + LOAN_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(100)
+            .build());
+        (context, contract, token_id, loan_id)
+    }
+
+    #[test]
+    fn test_fund_and_repay() {
+        let (mut context, mut contract, token_id, loan_id) = setup();
+        assert_eq!(contract.get_open_loans(None, None).len(), 1);
+        contract.fund_loan(loan_id);
+        assert_eq!(
+            contract.get_loan(loan_id).unwrap().status,
+            LoanStatus::Active
+        );
+        assert!(contract.get_open_loans(None, None).is_empty());
+        assert_eq!(
+            contract.nft_token(token_id.clone()).unwrap().owner_id,
+            accounts(0)
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(PRINCIPAL + 100 + LOAN_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(149)
+            .build());
+        contract.repay_loan(loan_id);
+        assert_eq!(
+            contract.get_loan(loan_id).unwrap().status,
+            LoanStatus::Repaid
+        );
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1));
+    }
+
+    #[test]
+    fn test_lender_claims_defaulted_loan() {
+        let (mut context, mut contract, token_id, loan_id) = setup();
+        contract.fund_loan(loan_id);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .block_timestamp(150)
+            .build());
+        contract.claim_defaulted_loan(loan_id);
+        assert_eq!(
+            contract.get_loan(loan_id).unwrap().status,
+            LoanStatus::Defaulted
+        );
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Loan is not in default yet")]
+    fn test_claim_before_deadline() {
+        let (mut context, mut contract, _, loan_id) = setup();
+        contract.fund_loan(loan_id);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .block_timestamp(149)
+            .build());
+        contract.claim_defaulted_loan(loan_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account charlie is blocked")]
+    fn test_blocked_lender_cannot_claim() {
+        let (mut context, mut contract, _, loan_id) = setup();
+        contract.fund_loan(loan_id);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.add_to_blocklist(accounts(2));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(150)
+            .build());
+        contract.claim_defaulted_loan(loan_id);
+    }
+
+    /// Mints "ticket" to `accounts(1)`, expiring one second after the first block.
+    fn mint_ticket(context: &mut VMContextBuilder, contract: &mut Contract) {
+        let mut ticket = sample_token_metadata();
+        ticket.expires_at = Some("1000".to_string());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(0)
+            .build());
+        contract.nft_mint("ticket".to_string(), accounts(1), ticket);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token has expired")]
+    fn test_request_loan_on_expired_token() {
+        let (mut context, mut contract, _, _) = setup();
+        mint_ticket(&mut context, &mut contract);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(LOAN_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(1_000_000_000)
+            .build());
+        contract.request_loan("ticket".to_string(), U128(PRINCIPAL), U128(100), U64(50));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token has expired")]
+    fn test_claim_expired_collateral() {
+        let (mut context, mut contract, _, _) = setup();
+        mint_ticket(&mut context, &mut contract);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(LOAN_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let loan_id =
+            contract.request_loan("ticket".to_string(), U128(PRINCIPAL), U128(100), U64(50));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(PRINCIPAL + LOAN_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(100)
+            .build());
+        contract.fund_loan(loan_id);
+
+        testing_env!(context
+            .attached_deposit(1)
+            .block_timestamp(1_000_000_000)
+            .build());
+        contract.claim_defaulted_loan(loan_id);
+    }
+}
@@ -15,22 +15,161 @@ NOTES:
   - To prevent the deployed contract from being modified or deleted, it should not have any access
     keys on its account.
 */
+use near_contract_standards::fungible_token::FungibleToken;
 use near_contract_standards::non_fungible_token::metadata::{
     NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata, NFT_METADATA_SPEC,
 };
-use near_contract_standards::non_fungible_token::{Token, TokenId};
+use near_contract_standards::non_fungible_token::events::NftMint;
+use near_contract_standards::non_fungible_token::{refund_deposit, Token, TokenId};
 use near_contract_standards::non_fungible_token::NonFungibleToken;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LazyOption;
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, Vector};
+use near_sdk::json_types::U128;
 use near_sdk::{
-    env, near_bindgen, AccountId, BorshStorageKey, PanicOnDefault, Promise, PromiseOrValue,
+    env, near_bindgen, require, AccountId, Balance, BorshStorageKey, PanicOnDefault, Promise,
+    PromiseOrValue, PublicKey,
 };
+use std::collections::{HashMap, HashSet};
+
+mod approval;
+mod battle;
+mod blocklist;
+mod breeding;
+mod check_in;
+mod checkpoints;
+mod crafting;
+mod distribution;
+mod enumeration;
+mod equipment;
+mod events;
+mod external_equipment;
+mod governance;
+mod lending;
+mod migrate;
+mod mint_pool;
+mod multi_token;
+mod nft_core;
+mod passes;
+mod recovery;
+mod rental;
+mod reveal;
+mod roles;
+mod stats;
+mod staking;
+mod svg;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test_utils;
+mod transfer_policy;
+mod utils;
+mod validity;
+mod vault;
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     tokens: NonFungibleToken,
     metadata: LazyOption<NFTContractMetadata>,
+    // approval id -> expiration block timestamp, for the approvals of a token that expire
+    approval_expirations: LookupMap<TokenId, HashMap<u64, u64>>,
+    // owner -> operator -> optional expiration block timestamp
+    operator_approvals: LookupMap<AccountId, HashMap<AccountId, Option<u64>>>,
+    rentals: LookupMap<TokenId, rental::Rental>,
+    rental_listings: LookupMap<TokenId, rental::RentalListing>,
+    blind_drop: LazyOption<reveal::BlindDrop>,
+    // tokens minted with the placeholder metadata of the blind drop
+    blind_tokens: LookupSet<TokenId>,
+    mint_pool: Option<mint_pool::MintPool>,
+    mint_pool_swaps: LookupMap<u64, u64>,
+    token_traits: LookupMap<TokenId, svg::WarriorTraits>,
+    warrior_stats: LookupMap<TokenId, stats::WarriorStats>,
+    // account allowed to award experience to warriors
+    game_server_id: Option<AccountId>,
+    battle_history: LookupMap<TokenId, Vec<battle::BattleRecord>>,
+    challenges: LookupMap<u64, battle::Challenge>,
+    next_challenge_id: u64,
+    recipes: LookupMap<u64, crafting::Recipe>,
+    next_recipe_id: u64,
+    // number of tokens minted by crafting, which make up their ids
+    crafted_count: u64,
+    breeding_config: Option<breeding::BreedingConfig>,
+    lineage: LookupMap<TokenId, breeding::Lineage>,
+    // number of tokens minted by breeding, which make up their ids
+    bred_count: u64,
+    // warrior -> tokens equipped onto it, held in the custody of the contract
+    equipment: LookupMap<TokenId, Vec<TokenId>>,
+    equipped_in: LookupMap<TokenId, TokenId>,
+    // warrior -> NFTs of other contracts attached to it
+    external_equipment: LookupMap<TokenId, Vec<external_equipment::ExternalToken>>,
+    staking_config: Option<staking::StakingConfig>,
+    stakes: LookupMap<TokenId, staking::Stake>,
+    staked_by_owner: LookupMap<AccountId, Vec<TokenId>>,
+    // rarity tier -> rewards accrued per staked warrior
+    reward_indexes: Vec<staking::RewardIndex>,
+    reward_indexes_updated_at: u64,
+    // yoctoNEAR set aside to pay staking rewards
+    reward_pool: Balance,
+    // account -> rewards in yoctoNEAR the reward pool could not cover
+    owed_rewards: LookupMap<AccountId, Balance>,
+    reward_points: LookupMap<AccountId, u128>,
+    // price of `nft_mint_default` in yoctoNEAR, on top of the storage of the token
+    mint_price: Balance,
+    proposals: LookupMap<u64, governance::Proposal>,
+    next_proposal_id: u64,
+    // (proposal id, token id) -> option the token voted for
+    token_votes: LookupMap<(u64, TokenId), u32>,
+    // token -> (block height, owner from that block on, `None` once burned)
+    owner_checkpoints: LookupMap<TokenId, Vector<(u64, Option<AccountId>)>>,
+    // account -> (block height, number of tokens owned from that block on)
+    supply_checkpoints: LookupMap<AccountId, Vector<(u64, u64)>>,
+    distributions: LookupMap<u64, distribution::Distribution>,
+    next_distribution_id: u64,
+    // (round id, token id) of the tokens which claimed their share of a round
+    distribution_claims: LookupSet<(u64, TokenId)>,
+    // fungible token contracts accepted for distribution rounds
+    distribution_fts: LookupSet<AccountId>,
+    pass_types: LookupMap<u64, passes::PassType>,
+    next_pass_type_id: u64,
+    // mint pass token -> its pass type
+    pass_tokens: LookupMap<TokenId, u64>,
+    used_passes: LookupSet<TokenId>,
+    // number of mint passes issued, which make up their ids
+    passes_minted: u64,
+    // the fractionalized token and its buyout
+    vault: Option<vault::Vault>,
+    // NEP-141 shares of the fractionalized token
+    shares: FungibleToken,
+    loans: LookupMap<u64, lending::Loan>,
+    next_loan_id: u64,
+    // NEP-245 item types and balances
+    mt_metadata: LookupMap<TokenId, TokenMetadata>,
+    mt_balances: LookupMap<(TokenId, AccountId), Balance>,
+    mt_supply: LookupMap<TokenId, Balance>,
+    verifiers: LookupSet<AccountId>,
+    event_series: LookupMap<String, check_in::EventSeries>,
+    // series id -> tickets of the series
+    series_tickets: LookupMap<String, Vec<TokenId>>,
+    // ticket -> its series id
+    ticket_series: LookupMap<TokenId, String>,
+    check_ins: LookupMap<TokenId, check_in::CheckIn>,
+    // holder -> key it signs check-in challenges with
+    ticket_keys: LookupMap<AccountId, PublicKey>,
+    transfer_policy: transfer_policy::TransferPolicy,
+    listed_receivers: LookupSet<AccountId>,
+    // token -> block timestamp it was minted at
+    minted_at: LookupMap<TokenId, u64>,
+    // token -> block timestamp its transfers are locked until
+    transfer_locks: LookupMap<TokenId, u64>,
+    blocklist: LookupSet<AccountId>,
+    recovery_account_id: Option<AccountId>,
+    recovery_delay: u64,
+    pending_admin_transfers: UnorderedMap<TokenId, recovery::PendingAdminTransfer>,
+    admin_transfer_disabled: bool,
+    roles: LookupMap<roles::Role, HashSet<AccountId>>,
+    paused: bool,
+    // NFT contracts whose tokens can be attached to warriors
+    equipment_contracts: LookupSet<AccountId>,
+    // account -> external NFTs released to it that could not be sent
+    unclaimed_external: LookupMap<AccountId, Vec<external_equipment::ExternalToken>>,
 }
 
 //const DATA_IMAGE_SVG_NEAR_ICON: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 288 288'%3E%3Cg id='l' data-name='l'%3E%3Cpath d='M187.58,79.81l-30.1,44.69a3.2,3.2,0,0,0,4.75,4.2L191.86,103a1.2,1.2,0,0,1,2,.91v80.46a1.2,1.2,0,0,1-2.12.77L102.18,77.93A15.35,15.35,0,0,0,90.47,72.5H87.34A15.34,15.34,0,0,0,72,87.84V201.16A15.34,15.34,0,0,0,87.34,216.5h0a15.35,15.35,0,0,0,13.08-7.31l30.1-44.69a3.2,3.2,0,0,0-4.75-4.2L96.14,186a1.2,1.2,0,0,1-2-.91V104.61a1.2,1.2,0,0,1,2.12-.77l89.55,107.23a15.35,15.35,0,0,0,11.71,5.43h3.13A15.34,15.34,0,0,0,216,201.16V87.84A15.34,15.34,0,0,0,200.66,72.5h0A15.35,15.35,0,0,0,187.58,79.81Z'/%3E%3C/g%3E%3C/svg%3E";
@@ -43,6 +182,57 @@ enum StorageKey {
     TokenMetadata,
     Enumeration,
     Approval,
+    ApprovalExpirations,
+    OperatorApprovals,
+    Rentals,
+    RentalListings,
+    BlindDrop,
+    BlindTokens,
+    MintPoolSwaps,
+    TokenTraits,
+    WarriorStats,
+    BattleHistory,
+    Challenges,
+    Recipes,
+    Lineage,
+    Equipment,
+    EquippedIn,
+    ExternalEquipment,
+    Stakes,
+    StakedByOwner,
+    RewardPoints,
+    Proposals,
+    TokenVotes,
+    OwnerCheckpoints,
+    SupplyCheckpoints,
+    Distributions,
+    DistributionClaims,
+    PassTypes,
+    PassTokens,
+    UsedPasses,
+    Shares,
+    Loans,
+    MtMetadata,
+    MtBalances,
+    MtSupply,
+    Verifiers,
+    EventSeries,
+    SeriesTickets,
+    TicketSeries,
+    CheckIns,
+    TicketKeys,
+    ListedReceivers,
+    MintedAt,
+    TransferLocks,
+    Blocklist,
+    PendingAdminTransfers,
+    Roles,
+    EquipmentContracts,
+    UnclaimedExternal,
+    OwedRewards,
+    DistributionFts,
+    OwnerCheckpointsOf { token_hash: [u8; 32] },
+    SupplyCheckpointsOf { account_hash: [u8; 32] },
 }
 
 #[near_bindgen]
@@ -69,16 +259,14 @@ impl Contract {
     pub fn new(owner_id: AccountId, metadata: NFTContractMetadata) -> Self {
         assert!(!env::state_exists(), "Already initialized");
         metadata.assert_valid();
-        Self {
-            tokens: NonFungibleToken::new(
-                StorageKey::NonFungibleToken,
-                owner_id,
-                Some(StorageKey::TokenMetadata),
-                Some(StorageKey::Enumeration),
-                Some(StorageKey::Approval),
-            ),
-            metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
-        }
+        let tokens = NonFungibleToken::new(
+            StorageKey::NonFungibleToken,
+            owner_id,
+            Some(StorageKey::TokenMetadata),
+            Some(StorageKey::Enumeration),
+            Some(StorageKey::Approval),
+        );
+        Self::with_state(tokens, LazyOption::new(StorageKey::Metadata, Some(&metadata)))
     }
 
     /// Mint a new token with ID=`token_id` belonging to `receiver_id`.
@@ -87,8 +275,7 @@ impl Contract {
     /// in this call. `self.tokens.mint` will also require it to be Some, since
     /// `StorageKey::TokenMetadata` was provided at initialization.
     ///
-    /// `self.tokens.mint` will enforce `predecessor_account_id` to equal the `owner_id` given in
-    /// initialization call to `new`.
+    /// Requires the `Minter` role.
     #[payable]
     pub fn nft_mint(
         &mut self,
@@ -96,7 +283,12 @@ impl Contract {
         receiver_id: AccountId,
         token_metadata: TokenMetadata,
     ) -> Token {
-        self.tokens.internal_mint(token_id, receiver_id, Some(token_metadata))
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(roles::Role::Minter);
+        self.assert_not_pool_id(&token_id);
+        let token = self.internal_mint_token(token_id, receiver_id, token_metadata);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        token
     }
 
     pub fn token_metadata() -> TokenMetadata {
@@ -117,36 +309,184 @@ impl Contract {
     }
 
 
+    /// Mint a new warrior with a random ID drawn from the mint pool, random stats and random
+    /// traits its art is generated from, or with the placeholder metadata while a blind drop is
+    /// waiting to be revealed. The attached deposit must cover the mint price, paid to the
+    /// contract owner, and the storage of the token.
     #[payable]
-    pub fn nft_mint_default(
+    pub fn nft_mint_default(&mut self, receiver_id: AccountId) -> Token {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_blocked(&env::predecessor_account_id());
+        let token = self.internal_mint_warrior(receiver_id);
+        self.internal_collect_mint_price(initial_storage_usage, self.mint_price);
+        token
+    }
+
+    /// Set the price of `nft_mint_default` in yoctoNEAR.
+    pub fn set_mint_price(&mut self, price: U128) {
+        self.assert_role(roles::Role::Treasurer);
+        self.mint_price = price.0;
+    }
+
+    pub fn get_mint_price(&self) -> U128 {
+        self.mint_price.into()
+    }
+}
+
+impl Contract {
+    /// Builds the contract around the tokens and the contract metadata, with the rest of the
+    /// state empty and every role granted to the contract owner.
+    pub(crate) fn with_state(
+        tokens: NonFungibleToken,
+        metadata: LazyOption<NFTContractMetadata>,
+    ) -> Self {
+        let owner_id = tokens.owner_id.clone();
+        let mut this = Self {
+            tokens,
+            metadata,
+            approval_expirations: LookupMap::new(StorageKey::ApprovalExpirations),
+            operator_approvals: LookupMap::new(StorageKey::OperatorApprovals),
+            rentals: LookupMap::new(StorageKey::Rentals),
+            rental_listings: LookupMap::new(StorageKey::RentalListings),
+            blind_drop: LazyOption::new(StorageKey::BlindDrop, None),
+            blind_tokens: LookupSet::new(StorageKey::BlindTokens),
+            mint_pool: None,
+            mint_pool_swaps: LookupMap::new(StorageKey::MintPoolSwaps),
+            token_traits: LookupMap::new(StorageKey::TokenTraits),
+            warrior_stats: LookupMap::new(StorageKey::WarriorStats),
+            game_server_id: None,
+            battle_history: LookupMap::new(StorageKey::BattleHistory),
+            challenges: LookupMap::new(StorageKey::Challenges),
+            next_challenge_id: 0,
+            recipes: LookupMap::new(StorageKey::Recipes),
+            next_recipe_id: 0,
+            crafted_count: 0,
+            breeding_config: None,
+            lineage: LookupMap::new(StorageKey::Lineage),
+            bred_count: 0,
+            equipment: LookupMap::new(StorageKey::Equipment),
+            equipped_in: LookupMap::new(StorageKey::EquippedIn),
+            external_equipment: LookupMap::new(StorageKey::ExternalEquipment),
+            staking_config: None,
+            stakes: LookupMap::new(StorageKey::Stakes),
+            staked_by_owner: LookupMap::new(StorageKey::StakedByOwner),
+            reward_indexes: vec![Default::default(); staking::RARITY_TIERS],
+            reward_indexes_updated_at: 0,
+            reward_pool: 0,
+            owed_rewards: LookupMap::new(StorageKey::OwedRewards),
+            reward_points: LookupMap::new(StorageKey::RewardPoints),
+            mint_price: 0,
+            proposals: LookupMap::new(StorageKey::Proposals),
+            next_proposal_id: 0,
+            token_votes: LookupMap::new(StorageKey::TokenVotes),
+            owner_checkpoints: LookupMap::new(StorageKey::OwnerCheckpoints),
+            supply_checkpoints: LookupMap::new(StorageKey::SupplyCheckpoints),
+            distributions: LookupMap::new(StorageKey::Distributions),
+            next_distribution_id: 0,
+            distribution_claims: LookupSet::new(StorageKey::DistributionClaims),
+            distribution_fts: LookupSet::new(StorageKey::DistributionFts),
+            pass_types: LookupMap::new(StorageKey::PassTypes),
+            next_pass_type_id: 0,
+            pass_tokens: LookupMap::new(StorageKey::PassTokens),
+            used_passes: LookupSet::new(StorageKey::UsedPasses),
+            passes_minted: 0,
+            vault: None,
+            shares: FungibleToken::new(StorageKey::Shares),
+            loans: LookupMap::new(StorageKey::Loans),
+            next_loan_id: 0,
+            mt_metadata: LookupMap::new(StorageKey::MtMetadata),
+            mt_balances: LookupMap::new(StorageKey::MtBalances),
+            mt_supply: LookupMap::new(StorageKey::MtSupply),
+            verifiers: LookupSet::new(StorageKey::Verifiers),
+            event_series: LookupMap::new(StorageKey::EventSeries),
+            series_tickets: LookupMap::new(StorageKey::SeriesTickets),
+            ticket_series: LookupMap::new(StorageKey::TicketSeries),
+            check_ins: LookupMap::new(StorageKey::CheckIns),
+            ticket_keys: LookupMap::new(StorageKey::TicketKeys),
+            transfer_policy: transfer_policy::TransferPolicy::default(),
+            listed_receivers: LookupSet::new(StorageKey::ListedReceivers),
+            minted_at: LookupMap::new(StorageKey::MintedAt),
+            transfer_locks: LookupMap::new(StorageKey::TransferLocks),
+            blocklist: LookupSet::new(StorageKey::Blocklist),
+            recovery_account_id: None,
+            recovery_delay: 0,
+            pending_admin_transfers: UnorderedMap::new(StorageKey::PendingAdminTransfers),
+            admin_transfer_disabled: false,
+            roles: LookupMap::new(StorageKey::Roles),
+            paused: false,
+            equipment_contracts: LookupSet::new(StorageKey::EquipmentContracts),
+            unclaimed_external: LookupMap::new(StorageKey::UnclaimedExternal),
+        };
+        for role in roles::Role::ALL {
+            this.internal_grant_role(role, &owner_id);
+        }
+        this
+    }
+
+    /// Mint a warrior as `nft_mint_default` does, without handling the attached deposit.
+    pub(crate) fn internal_mint_warrior(&mut self, receiver_id: AccountId) -> Token {
+        let token_id = self.internal_draw_token_id();
+        self.warrior_stats
+            .insert(&token_id, &stats::WarriorStats::random(&token_id));
+        let token_metadata = match self.blind_drop_placeholder() {
+            Some(placeholder) => {
+                self.blind_tokens.insert(&token_id);
+                placeholder
+            }
+            None => {
+                self.token_traits
+                    .insert(&token_id, &svg::WarriorTraits::random(&token_id));
+                // the media is rendered from the traits when the token is viewed
+                TokenMetadata {
+                    media: None,
+                    ..Self::token_metadata()
+                }
+            }
+        };
+        self.internal_mint_token(token_id, receiver_id, token_metadata)
+    }
+
+    /// Requires the attached deposit to cover `price` and the storage used since
+    /// `initial_storage_usage`, pays the price to the contract owner and refunds the rest.
+    pub(crate) fn internal_collect_mint_price(&self, initial_storage_usage: u64, price: Balance) {
+        if price > 0 {
+            Promise::new(self.tokens.owner_id.clone()).transfer(price);
+        }
+        utils::refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            price,
+        );
+    }
+
+    /// Mint a new token without checking the caller or handling the attached deposit, which
+    /// lets the caller account for the storage of anything recorded alongside the token.
+    pub(crate) fn internal_mint_token(
         &mut self,
         token_id: TokenId,
-        receiver_id: AccountId,
+        token_owner_id: AccountId,
+        token_metadata: TokenMetadata,
     ) -> Token {
-        self.tokens.internal_mint(token_id, receiver_id, Some(
-
-            TokenMetadata {
-                title: Some("NSeven Limited Edition".into()),
-                description: Some("Limited Edition original NSeven NEARWarrior".into()),
-                media: Some("https://i.ibb.co/1n9fjsd/1589875387-16.png".to_string()),
-                media_hash: None,
-                copies: Some(1u64),
-                issued_at: None,
-                expires_at: None,
-                starts_at: None,
-                updated_at: None,
-                extra: None,
-                reference: None,
-                reference_hash: None,
-            }
-
-        ))
-    }    
+        self.assert_not_paused();
+        validity::assert_valid_window(&token_metadata);
+        self.assert_not_blocked(&token_owner_id);
+        let token = self.tokens.internal_mint_with_refund(
+            token_id,
+            token_owner_id,
+            Some(token_metadata),
+            None,
+        );
+        self.internal_checkpoint_owner(&token.token_id, None, Some(&token.owner_id));
+        self.minted_at.insert(&token.token_id, &env::block_timestamp());
+        NftMint {
+            owner_id: &token.owner_id,
+            token_ids: &[&token.token_id],
+            memo: None,
+        }
+        .emit();
+        token
+    }
 }
 
-near_contract_standards::impl_non_fungible_token_core!(Contract, tokens);
-near_contract_standards::impl_non_fungible_token_approval!(Contract, tokens);
-near_contract_standards::impl_non_fungible_token_enumeration!(Contract, tokens);
 
 #[near_bindgen]
 impl NonFungibleTokenMetadataProvider for Contract {
@@ -157,13 +497,15 @@ impl NonFungibleTokenMetadataProvider for Contract {
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
+    use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApproval;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
     use near_sdk::test_utils::{accounts, VMContextBuilder};
     use near_sdk::testing_env;
     use std::collections::HashMap;
 
     use super::*;
 
-    const MINT_STORAGE_COST: u128 = 5870000000000000000000;
+    const MINT_STORAGE_COST: u128 = 11000000000000000000000;
 
     fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
         let mut builder = VMContextBuilder::new();
@@ -195,7 +537,7 @@ mod tests {
     fn test_new() {
         let mut context = get_context(accounts(1));
         testing_env!(context.build());
-        let contract = Contract::new_default_meta(accounts(1).into());
+        let contract = Contract::new_default_meta(accounts(1));
         testing_env!(context.is_view(true).build());
         assert_eq!(contract.nft_token("1".to_string()), None);
     }
@@ -212,7 +554,7 @@ mod tests {
     fn test_mint() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -223,7 +565,7 @@ mod tests {
         let token_id = "0".to_string();
         let token = contract.nft_mint(token_id.clone(), accounts(0), sample_token_metadata());
         assert_eq!(token.token_id, token_id);
-        assert_eq!(token.owner_id, accounts(0).to_string());
+        assert_eq!(token.owner_id, accounts(0));
         assert_eq!(token.metadata.unwrap(), sample_token_metadata());
         assert_eq!(token.approved_account_ids.unwrap(), HashMap::new());
     }
@@ -232,7 +574,7 @@ mod tests {
     fn test_transfer() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -257,7 +599,7 @@ mod tests {
             .build());
         if let Some(token) = contract.nft_token(token_id.clone()) {
             assert_eq!(token.token_id, token_id);
-            assert_eq!(token.owner_id, accounts(1).to_string());
+            assert_eq!(token.owner_id, accounts(1));
             assert_eq!(token.metadata.unwrap(), sample_token_metadata());
             assert_eq!(token.approved_account_ids.unwrap(), HashMap::new());
         } else {
@@ -269,7 +611,7 @@ mod tests {
     fn test_approve() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -282,7 +624,7 @@ mod tests {
         // alice approves bob
         testing_env!(context
             .storage_usage(env::storage_usage())
-            .attached_deposit(150000000000000000000)
+            .attached_deposit(2000000000000000000000)
             .predecessor_account_id(accounts(0))
             .build());
         contract.nft_approve(token_id.clone(), accounts(1), None);
@@ -300,7 +642,7 @@ mod tests {
     fn test_revoke() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -313,7 +655,7 @@ mod tests {
         // alice approves bob
         testing_env!(context
             .storage_usage(env::storage_usage())
-            .attached_deposit(150000000000000000000)
+            .attached_deposit(2000000000000000000000)
             .predecessor_account_id(accounts(0))
             .build());
         contract.nft_approve(token_id.clone(), accounts(1), None);
@@ -338,7 +680,7 @@ mod tests {
     fn test_revoke_all() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -351,7 +693,7 @@ mod tests {
         // alice approves bob
         testing_env!(context
             .storage_usage(env::storage_usage())
-            .attached_deposit(150000000000000000000)
+            .attached_deposit(2000000000000000000000)
             .predecessor_account_id(accounts(0))
             .build());
         contract.nft_approve(token_id.clone(), accounts(1), None);
@@ -0,0 +1,113 @@
+//! Upgrade from the first deployed version of the contract, which only held the tokens and the
+//! contract metadata.
+use crate::*;
+
+/// State layout of the first deployed version.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BaselineContract {
+    tokens: NonFungibleToken,
+    metadata: LazyOption<NFTContractMetadata>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Migrate the state of the first deployed version after deploying this code on top of it.
+    /// Tokens and metadata are kept and the contract owner gets every role. Existing tokens are
+    /// recorded as owned by their current owner since the first block and as minted at time
+    /// zero, so that they count in snapshots and are past any holding period. The rest of the
+    /// state starts empty.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: BaselineContract =
+            env::state_read().unwrap_or_else(|| env::panic_str("No state to migrate"));
+        let mut contract = Self::with_state(old.tokens, old.metadata);
+        let owners: Vec<(TokenId, AccountId)> = contract.tokens.owner_by_id.iter().collect();
+        for (token_id, _) in &owners {
+            contract.minted_at.insert(token_id, &0);
+        }
+        contract.internal_seed_checkpoints(&owners);
+        contract
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::BaselineContract;
+    use crate::roles::Role;
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_STORAGE_COST};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+
+    /// Writes the state of the first deployed version with token "0" owned by `accounts(1)`.
+    fn write_baseline_state() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.attached_deposit(MINT_STORAGE_COST).build());
+        let mut tokens = NonFungibleToken::new(
+            StorageKey::NonFungibleToken,
+            accounts(0),
+            Some(StorageKey::TokenMetadata),
+            Some(StorageKey::Enumeration),
+            Some(StorageKey::Approval),
+        );
+        tokens.internal_mint("0".to_string(), accounts(1), Some(sample_token_metadata()));
+        let metadata = NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: "Baseline".to_string(),
+            symbol: "BASE".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        };
+        env::state_write(&BaselineContract {
+            tokens,
+            metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+        });
+    }
+
+    #[test]
+    fn test_migrate_baseline_state() {
+        write_baseline_state();
+        let contract = Contract::migrate();
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(1)
+        );
+        assert_eq!(contract.nft_metadata().name, "Baseline");
+        assert!(contract.has_role(Role::Admin, accounts(0)));
+        assert!(!contract.is_paused());
+        assert_eq!(
+            contract.nft_owner_at("0".to_string(), U64(0)),
+            Some(accounts(1))
+        );
+        assert_eq!(
+            contract.nft_supply_for_owner_at(accounts(1), U64(0)),
+            U128(1)
+        );
+    }
+
+    #[test]
+    fn test_claim_distribution_after_migration() {
+        write_baseline_state();
+        let mut contract = Contract::migrate();
+        let mut context = get_context(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1_000)
+            .block_index(10)
+            .build());
+        let round_id = contract.create_distribution();
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(1))
+            .block_index(11)
+            .build());
+        assert_eq!(contract.claim(round_id, vec!["0".to_string()]), U128(1_000));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::roles::Role;
+use crate::utils::random_u64;
+use crate::*;
+
+/// Token ids `0..max_supply` still available for minting, in random order.
+///
+/// The pool is a lazy Fisher-Yates shuffle: the first `remaining` slots hold the unminted ids,
+/// and a slot holds its own index unless an id was swapped into it, in which case the id is
+/// recorded in `mint_pool_swaps`. Drawing a slot moves the last one into its place, so every
+/// mint is O(1) and every unminted id is equally likely.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MintPool {
+    pub max_supply: u64,
+    pub remaining: u64,
+}
+
+impl Contract {
+    fn pool_slot(&self, slot: u64) -> u64 {
+        self.mint_pool_swaps.get(&slot).unwrap_or(slot)
+    }
+
+    /// Draw a random unminted token id from the pool. Ids minted outside of the pool, such as
+    /// tokens that existed before it was set up, are dropped from it when drawn.
+    pub(crate) fn internal_draw_token_id(&mut self) -> TokenId {
+        let mut pool = self
+            .mint_pool
+            .take()
+            .unwrap_or_else(|| env::panic_str("Mint pool is not initialized"));
+        let token_id = loop {
+            require!(pool.remaining > 0, "All tokens have been minted");
+            let slot = random_u64(&pool.remaining.to_le_bytes()) % pool.remaining;
+            let last = pool.remaining - 1;
+            let index = self.pool_slot(slot);
+            if slot != last {
+                let last_index = self.pool_slot(last);
+                self.mint_pool_swaps.insert(&slot, &last_index);
+            }
+            self.mint_pool_swaps.remove(&last);
+            pool.remaining = last;
+
+            let token_id = index.to_string();
+            if self.tokens.owner_by_id.get(&token_id).is_none() {
+                break token_id;
+            }
+        };
+        self.mint_pool = Some(pool);
+        token_id
+    }
+
+    /// Panics if `token_id` is one of the ids `0..max_supply` the mint pool assigns.
+    pub(crate) fn assert_not_pool_id(&self, token_id: &TokenId) {
+        if let Some(pool) = self.mint_pool.as_ref() {
+            let reserved = token_id.parse::<u64>().map_or(false, |id| {
+                id < pool.max_supply && id.to_string() == *token_id
+            });
+            require!(!reserved, "Token id is reserved by the mint pool");
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set up the pool of token ids `0..max_supply` that `nft_mint_default` assigns from.
+    pub fn init_mint_pool(&mut self, max_supply: u64) {
+        self.assert_role(Role::Minter);
+        require!(self.mint_pool.is_none(), "Mint pool already initialized");
+        require!(max_supply > 0, "Max supply must be positive");
+        self.mint_pool = Some(MintPool {
+            max_supply,
+            remaining: max_supply,
+        });
+    }
+
+    /// Returns the max supply of the mint pool and the number of token ids left in it.
+    pub fn get_mint_pool(&self) -> Option<(u64, u64)> {
+        self.mint_pool
+            .as_ref()
+            .map(|pool| (pool.max_supply, pool.remaining))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_mint_assigns_every_id_once() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(5);
+
+        let mut token_ids = HashSet::new();
+        for i in 0..5u8 {
+            testing_env!(context
+                .storage_usage(env::storage_usage())
+                .attached_deposit(MINT_DEPOSIT)
+                .predecessor_account_id(accounts(1))
+                .random_seed([i; 32])
+                .build());
+            let token = contract.nft_mint_default(accounts(1));
+            assert!(token.token_id.parse::<u64>().unwrap() < 5);
+            token_ids.insert(token.token_id);
+        }
+        assert_eq!(token_ids.len(), 5);
+        assert_eq!(contract.get_mint_pool(), Some((5, 0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "All tokens have been minted")]
+    fn test_mint_beyond_max_supply() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(1);
+        for _ in 0..2 {
+            testing_env!(context
+                .storage_usage(env::storage_usage())
+                .attached_deposit(MINT_DEPOSIT)
+                .build());
+            contract.nft_mint_default(accounts(1));
+        }
+    }
+
+    #[test]
+    fn test_pool_skips_ids_minted_before() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.attached_deposit(MINT_DEPOSIT).build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        for token_id in ["0", "1"] {
+            testing_env!(context.storage_usage(env::storage_usage()).build());
+            contract.nft_mint(token_id.to_string(), accounts(1), sample_token_metadata());
+        }
+        contract.init_mint_pool(3);
+
+        testing_env!(context.storage_usage(env::storage_usage()).build());
+        assert_eq!(contract.nft_mint_default(accounts(1)).token_id, "2");
+    }
+
+    #[test]
+    #[should_panic(expected = "Token id is reserved by the mint pool")]
+    fn test_nft_mint_pool_id() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.attached_deposit(MINT_DEPOSIT).build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(3);
+        contract.nft_mint("1".to_string(), accounts(1), sample_token_metadata());
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint pool is not initialized")]
+    fn test_mint_without_pool() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.nft_mint_default(accounts(1));
+    }
+}
@@ -0,0 +1,345 @@
+//! NEP-245 multi tokens for fungible game items like potions, held in balances per account
+//! next to the warriors. Item types are created and minted by minters, and new
+//! balance entries are paid for by the attached deposit like any other storage.
+use crate::events::emit_event;
+use crate::roles::Role;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{assert_one_yocto, Balance};
+
+const MT_STANDARD: &str = "nep245";
+const MT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct MtMint<'a> {
+    owner_id: &'a AccountId,
+    token_ids: &'a [TokenId],
+    amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct MtBurn<'a> {
+    owner_id: &'a AccountId,
+    token_ids: &'a [TokenId],
+    amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct MtTransfer<'a> {
+    old_owner_id: &'a AccountId,
+    new_owner_id: &'a AccountId,
+    token_ids: &'a [TokenId],
+    amounts: &'a [U128],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<&'a str>,
+}
+
+impl Contract {
+    fn assert_mt_exists(&self, token_id: &TokenId) {
+        require!(
+            self.mt_metadata.get(token_id).is_some(),
+            format!("Item type {} not found", token_id)
+        );
+    }
+
+    fn internal_mt_deposit(&mut self, token_id: &TokenId, account_id: &AccountId, amount: Balance) {
+        let key = (token_id.clone(), account_id.clone());
+        let balance = self.mt_balances.get(&key).unwrap_or(0);
+        let balance = balance
+            .checked_add(amount)
+            .unwrap_or_else(|| env::panic_str("Balance overflow"));
+        self.mt_balances.insert(&key, &balance);
+    }
+
+    /// Removes the balance entry when it drops to zero, freeing its storage.
+    fn internal_mt_withdraw(
+        &mut self,
+        token_id: &TokenId,
+        account_id: &AccountId,
+        amount: Balance,
+    ) {
+        let key = (token_id.clone(), account_id.clone());
+        let balance = self.mt_balances.get(&key).unwrap_or(0);
+        let balance = balance
+            .checked_sub(amount)
+            .unwrap_or_else(|| env::panic_str(&format!("Not enough {} to spend", token_id)));
+        if balance == 0 {
+            self.mt_balances.remove(&key);
+        } else {
+            self.mt_balances.insert(&key, &balance);
+        }
+    }
+
+    fn internal_mt_batch_transfer(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_ids: &[TokenId],
+        amounts: &[U128],
+        memo: Option<String>,
+    ) {
+        self.assert_not_paused();
+        require!(sender_id != receiver_id, "Sender and receiver must differ");
+        self.assert_not_blocked(sender_id);
+        self.assert_not_blocked(receiver_id);
+        require!(
+            token_ids.len() == amounts.len(),
+            "Token ids and amounts must have the same length"
+        );
+        for (token_id, amount) in token_ids.iter().zip(amounts) {
+            require!(amount.0 > 0, "Amount must be positive");
+            self.internal_mt_withdraw(token_id, sender_id, amount.0);
+            self.internal_mt_deposit(token_id, receiver_id, amount.0);
+        }
+        MtTransfer {
+            old_owner_id: sender_id,
+            new_owner_id: receiver_id,
+            token_ids,
+            amounts,
+            memo: memo.as_deref(),
+        }
+        .emit();
+    }
+}
+
+impl MtMint<'_> {
+    fn emit(self) {
+        emit_event(MT_STANDARD, MT_VERSION, "mt_mint", [self]);
+    }
+}
+
+impl MtBurn<'_> {
+    fn emit(self) {
+        emit_event(MT_STANDARD, MT_VERSION, "mt_burn", [self]);
+    }
+}
+
+impl MtTransfer<'_> {
+    fn emit(self) {
+        emit_event(MT_STANDARD, MT_VERSION, "mt_transfer", [self]);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Create an item type with id `token_id`. Requires the `Minter` role.
+    #[payable]
+    pub fn mt_create(&mut self, token_id: TokenId, metadata: TokenMetadata) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Minter);
+        require!(
+            self.mt_metadata.insert(&token_id, &metadata).is_none(),
+            "Item type already exists"
+        );
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Mint `amount` items of type `token_id` to `receiver_id`. Requires the `Minter` role, and
+    /// the attached deposit must cover the storage of a new balance.
+    #[payable]
+    pub fn mt_mint(
+        &mut self,
+        token_id: TokenId,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Minter);
+        self.assert_not_paused();
+        self.assert_mt_exists(&token_id);
+        self.assert_not_blocked(&receiver_id);
+        require!(amount.0 > 0, "Amount must be positive");
+        self.internal_mt_deposit(&token_id, &receiver_id, amount.0);
+        let supply = self.mt_supply.get(&token_id).unwrap_or(0);
+        self.mt_supply.insert(&token_id, &(supply + amount.0));
+        MtMint {
+            owner_id: &receiver_id,
+            token_ids: &[token_id],
+            amounts: &[amount],
+            memo: memo.as_deref(),
+        }
+        .emit();
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Burn `amount` items of type `token_id` of the predecessor, as when using a consumable.
+    #[payable]
+    pub fn mt_burn(&mut self, token_id: TokenId, amount: U128, memo: Option<String>) {
+        assert_one_yocto();
+        require!(amount.0 > 0, "Amount must be positive");
+        let owner_id = env::predecessor_account_id();
+        self.internal_mt_withdraw(&token_id, &owner_id, amount.0);
+        let supply = self.mt_supply.get(&token_id).unwrap_or(0);
+        self.mt_supply.insert(&token_id, &(supply - amount.0));
+        MtBurn {
+            owner_id: &owner_id,
+            token_ids: &[token_id],
+            amounts: &[amount],
+            memo: memo.as_deref(),
+        }
+        .emit();
+    }
+
+    /// Transfer `amount` items of type `token_id` from the predecessor to `receiver_id`.
+    /// Requires at least 1 yoctoNEAR, and enough to cover the storage of a new balance of the
+    /// receiver. Approvals are not supported.
+    #[payable]
+    pub fn mt_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        amount: U128,
+        approval: Option<(AccountId, u64)>,
+        memo: Option<String>,
+    ) {
+        self.mt_batch_transfer(
+            receiver_id,
+            vec![token_id],
+            vec![amount],
+            approval.map(|approval| vec![Some(approval)]),
+            memo,
+        )
+    }
+
+    /// Transfer several item types at once from the predecessor to `receiver_id`.
+    #[payable]
+    pub fn mt_batch_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<(AccountId, u64)>>>,
+        memo: Option<String>,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        require!(
+            env::attached_deposit() >= 1,
+            "Requires attached deposit of at least 1 yoctoNEAR"
+        );
+        require!(
+            approvals.map_or(true, |approvals| approvals.iter().all(Option::is_none)),
+            "Approvals are not supported for items"
+        );
+        let sender_id = env::predecessor_account_id();
+        self.internal_mt_batch_transfer(&sender_id, &receiver_id, &token_ids, &amounts, memo);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    pub fn mt_balance_of(&self, account_id: AccountId, token_id: TokenId) -> U128 {
+        U128(self.mt_balances.get(&(token_id, account_id)).unwrap_or(0))
+    }
+
+    pub fn mt_batch_balance_of(&self, account_id: AccountId, token_ids: Vec<TokenId>) -> Vec<U128> {
+        token_ids
+            .into_iter()
+            .map(|token_id| self.mt_balance_of(account_id.clone(), token_id))
+            .collect()
+    }
+
+    /// Returns the number of items of type `token_id` in circulation.
+    pub fn mt_supply(&self, token_id: TokenId) -> Option<U128> {
+        self.mt_metadata
+            .get(&token_id)
+            .map(|_| U128(self.mt_supply.get(&token_id).unwrap_or(0)))
+    }
+
+    pub fn mt_metadata(&self, token_id: TokenId) -> Option<TokenMetadata> {
+        self.mt_metadata.get(&token_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_STORAGE_COST};
+    use crate::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.mt_create("potion".to_string(), sample_token_metadata());
+        contract.mt_mint("potion".to_string(), accounts(1), U128(10), None);
+        (context, contract)
+    }
+
+    #[test]
+    fn test_transfer_and_burn() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.mt_transfer(accounts(2), "potion".to_string(), U128(4), None, None);
+        assert_eq!(
+            get_logs(),
+            vec![concat!(
+                r#"EVENT_JSON:{"standard":"nep245","version":"1.0.0","event":"mt_transfer","#,
+                r#""data":[{"old_owner_id":"bob","new_owner_id":"charlie","#,
+                r#""token_ids":["potion"],"amounts":["4"]}]}"#
+            )]
+        );
+        assert_eq!(
+            contract.mt_batch_balance_of(accounts(1), vec!["potion".to_string()]),
+            vec![U128(6)]
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.mt_burn("potion".to_string(), U128(4), None);
+        assert_eq!(
+            contract.mt_balance_of(accounts(2), "potion".to_string()),
+            U128(0)
+        );
+        assert_eq!(contract.mt_supply("potion".to_string()), Some(U128(6)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough potion to spend")]
+    fn test_transfer_more_than_balance() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.mt_batch_transfer(
+            accounts(2),
+            vec!["potion".to_string()],
+            vec![U128(11)],
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires the Minter role")]
+    fn test_only_owner_can_mint() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.mt_mint("potion".to_string(), accounts(1), U128(10), None);
+    }
+}
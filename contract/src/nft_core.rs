@@ -0,0 +1,308 @@
+use crate::*;
+use near_contract_standards::non_fungible_token::core::{
+    ext_receiver, NonFungibleTokenCore, NonFungibleTokenResolver,
+};
+use near_contract_standards::non_fungible_token::events::{NftBurn, NftTransfer};
+use near_sdk::{assert_one_yocto, ext_contract, require, Balance, Gas};
+
+const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(5_000_000_000_000);
+const GAS_FOR_NFT_TRANSFER_CALL: Gas = Gas(25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER.0);
+
+const NO_DEPOSIT: Balance = 0;
+
+#[ext_contract(ext_self)]
+trait NFTResolver {
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+    ) -> bool;
+}
+
+impl Contract {
+    /// The token as returned by the view methods, with its metadata resolved for the current
+    /// state of the contract.
+    pub(crate) fn internal_view_token(&self, token_id: TokenId, owner_id: AccountId) -> Token {
+        let metadata = self
+            .tokens
+            .token_metadata_by_id
+            .as_ref()
+            .and_then(|by_id| by_id.get(&token_id))
+            .map(|metadata| self.internal_reveal_metadata(&token_id, metadata))
+            .map(|metadata| self.internal_render_media(&token_id, metadata))
+            .map(|metadata| self.internal_attach_stats(&token_id, metadata));
+        let approved_account_ids = Some(
+            self.tokens
+                .approvals_by_id
+                .as_ref()
+                .and_then(|by_id| by_id.get(&token_id))
+                .unwrap_or_default(),
+        );
+        Token {
+            token_id,
+            owner_id,
+            metadata,
+            approved_account_ids,
+        }
+    }
+
+    /// Transfer from current owner to `receiver_id`, checking that `sender_id` is the owner,
+    /// holds an unexpired approval for the token or is an operator for all of the owner's tokens.
+    /// Clears approvals and returns the previous owner and approvals.
+    pub(crate) fn internal_transfer(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) -> (AccountId, Option<HashMap<AccountId, u64>>) {
+        self.assert_not_paused();
+        self.assert_not_equipped(token_id);
+        self.assert_not_staked(token_id);
+        self.assert_active(token_id);
+        self.assert_not_checked_in(token_id);
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+        for account_id in [sender_id, &owner_id, receiver_id] {
+            self.assert_not_blocked(account_id);
+        }
+
+        // clear approvals, this will be rolled back by a panic if sending fails
+        let approved_account_ids = self
+            .tokens
+            .approvals_by_id
+            .as_mut()
+            .and_then(|by_id| by_id.remove(token_id));
+
+        let authorized_id = if sender_id != &owner_id {
+            let actual_approval_id = approved_account_ids
+                .as_ref()
+                .and_then(|ids| ids.get(sender_id))
+                .copied();
+            match actual_approval_id {
+                Some(actual_approval_id) => {
+                    require!(
+                        approval_id.is_none() || approval_id == Some(actual_approval_id),
+                        format!(
+                            "The actual approval_id {} is different from the given approval_id {:?}",
+                            actual_approval_id, approval_id
+                        )
+                    );
+                    require!(
+                        !self.is_approval_expired(token_id, actual_approval_id),
+                        "Approval expired"
+                    );
+                }
+                None => {
+                    require!(
+                        approval_id.is_none() && self.is_operator_for(&owner_id, sender_id),
+                        "Sender not approved"
+                    );
+                }
+            }
+            Some(sender_id)
+        } else {
+            None
+        };
+
+        require!(
+            &owner_id != receiver_id,
+            "Current and next owner must differ"
+        );
+
+        self.internal_clear_rental(token_id);
+        self.tokens
+            .internal_transfer_unguarded(token_id, &owner_id, receiver_id);
+        self.internal_checkpoint_owner(token_id, Some(&owner_id), Some(receiver_id));
+
+        NftTransfer {
+            old_owner_id: &owner_id,
+            new_owner_id: receiver_id,
+            token_ids: &[token_id],
+            authorized_id,
+            memo: memo.as_deref(),
+        }
+        .emit();
+
+        (owner_id, approved_account_ids)
+    }
+
+    /// Moves a token between accounts without any checks, as when the contract takes or
+    /// releases custody of it.
+    pub(crate) fn internal_move_token(
+        &mut self,
+        token_id: &TokenId,
+        from: &AccountId,
+        to: &AccountId,
+        memo: &str,
+    ) {
+        self.tokens.internal_transfer_unguarded(token_id, from, to);
+        self.internal_checkpoint_owner(token_id, Some(from), Some(to));
+        NftTransfer {
+            old_owner_id: from,
+            new_owner_id: to,
+            token_ids: &[token_id],
+            authorized_id: None,
+            memo: Some(memo),
+        }
+        .emit();
+    }
+
+    /// Removes a token along with everything recorded about it and returns its last owner.
+    /// Staked tokens must be unstaked first. Does not check the caller, nor handle the storage
+    /// it frees.
+    pub(crate) fn internal_burn(&mut self, token_id: &TokenId, memo: Option<&str>) -> AccountId {
+        self.assert_not_staked(token_id);
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .remove(token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+        self.internal_release_equipment(token_id, &owner_id);
+        self.internal_release_external_equipment(token_id, &owner_id);
+        if let Some(by_id) = self.tokens.token_metadata_by_id.as_mut() {
+            by_id.remove(token_id);
+        }
+        if let Some(tokens_per_owner) = self.tokens.tokens_per_owner.as_mut() {
+            let mut owner_tokens = tokens_per_owner.get(&owner_id).unwrap();
+            owner_tokens.remove(token_id);
+            if owner_tokens.is_empty() {
+                tokens_per_owner.remove(&owner_id);
+            } else {
+                tokens_per_owner.insert(&owner_id, &owner_tokens);
+            }
+        }
+        if let Some(by_id) = self.tokens.approvals_by_id.as_mut() {
+            by_id.remove(token_id);
+        }
+        if let Some(by_id) = self.tokens.next_approval_id_by_id.as_mut() {
+            by_id.remove(token_id);
+        }
+        self.approval_expirations.remove(token_id);
+        self.internal_clear_rental(token_id);
+        self.blind_tokens.remove(token_id);
+        self.token_traits.remove(token_id);
+        self.warrior_stats.remove(token_id);
+        self.battle_history.remove(token_id);
+        self.lineage.remove(token_id);
+        self.pass_tokens.remove(token_id);
+        self.used_passes.remove(token_id);
+        self.internal_clear_check_in(token_id);
+        self.minted_at.remove(token_id);
+        self.transfer_locks.remove(token_id);
+        self.pending_admin_transfers.remove(token_id);
+
+        self.internal_checkpoint_owner(token_id, Some(&owner_id), None);
+
+        NftBurn {
+            owner_id: &owner_id,
+            token_ids: &[token_id],
+            authorized_id: None,
+            memo,
+        }
+        .emit();
+
+        owner_id
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenCore for Contract {
+    #[payable]
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        self.assert_transfer_allowed(&token_id, &receiver_id);
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer(&sender_id, &receiver_id, &token_id, approval_id, memo);
+        self.approval_expirations.remove(&token_id);
+    }
+
+    #[payable]
+    fn nft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        assert_one_yocto();
+        require!(
+            env::prepaid_gas() > GAS_FOR_NFT_TRANSFER_CALL,
+            "More gas is required"
+        );
+        self.assert_transfer_allowed(&token_id, &receiver_id);
+        let sender_id = env::predecessor_account_id();
+        // expirations of the old approvals are kept until the transfer is resolved, so that
+        // they still apply if the approvals are restored
+        let (old_owner, old_approvals) =
+            self.internal_transfer(&sender_id, &receiver_id, &token_id, approval_id, memo);
+        ext_receiver::nft_on_transfer(
+            sender_id,
+            old_owner.clone(),
+            token_id.clone(),
+            msg,
+            receiver_id.clone(),
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
+        )
+        .then(ext_self::nft_resolve_transfer(
+            old_owner,
+            receiver_id,
+            token_id,
+            old_approvals,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        let owner_id = self.tokens.owner_by_id.get(&token_id)?;
+        Some(self.internal_view_token(token_id, owner_id))
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenResolver for Contract {
+    #[private]
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+    ) -> bool {
+        let old_approval_ids: Vec<u64> = approved_account_ids
+            .iter()
+            .flat_map(|ids| ids.values().copied())
+            .collect();
+        let transferred = self.tokens.nft_resolve_transfer(
+            previous_owner_id.clone(),
+            receiver_id.clone(),
+            token_id.clone(),
+            approved_account_ids,
+        );
+        if !transferred {
+            self.internal_checkpoint_owner(&token_id, Some(&receiver_id), Some(&previous_owner_id));
+        }
+        // drop the expirations of whichever approvals did not survive the transfer
+        self.internal_retain_approval_expirations(&token_id, |approval_id| {
+            old_approval_ids.contains(approval_id) != transferred
+        });
+        transferred
+    }
+}
@@ -0,0 +1,185 @@
+use crate::roles::Role;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// A kind of mint pass, issued as tokens of this contract and redeemable for a warrior of the
+/// mint pool drop once the owner opens redemption.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PassType {
+    /// Metadata of the pass tokens.
+    pub metadata: TokenMetadata,
+    /// Price of a mint with the pass in yoctoNEAR, 0 for a free mint.
+    pub redeem_price: U128,
+    /// Whether redeeming burns the pass, or keeps it marked as used.
+    pub burn_on_redeem: bool,
+    pub redeemable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PassView {
+    pub pass_type_id: u64,
+    pub used: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Register a pass type and return its id.
+    pub fn add_pass_type(&mut self, pass_type: PassType) -> u64 {
+        self.assert_role(Role::Minter);
+        let pass_type_id = self.next_pass_type_id;
+        self.next_pass_type_id += 1;
+        self.pass_types.insert(&pass_type_id, &pass_type);
+        pass_type_id
+    }
+
+    /// Open or close the redemption of the passes of a type.
+    pub fn set_pass_redeemable(&mut self, pass_type_id: u64, redeemable: bool) {
+        self.assert_role(Role::Minter);
+        let mut pass_type = self
+            .pass_types
+            .get(&pass_type_id)
+            .unwrap_or_else(|| env::panic_str("Pass type not found"));
+        pass_type.redeemable = redeemable;
+        self.pass_types.insert(&pass_type_id, &pass_type);
+    }
+
+    pub fn get_pass_type(&self, pass_type_id: u64) -> Option<PassType> {
+        self.pass_types.get(&pass_type_id)
+    }
+
+    /// Issue a mint pass of the given type to `receiver_id`.
+    #[payable]
+    pub fn nft_mint_pass(&mut self, pass_type_id: u64, receiver_id: AccountId) -> Token {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Minter);
+        let pass_type = self
+            .pass_types
+            .get(&pass_type_id)
+            .unwrap_or_else(|| env::panic_str("Pass type not found"));
+        let token_id = format!("pass-{}", self.passes_minted);
+        self.passes_minted += 1;
+        self.pass_tokens.insert(&token_id, &pass_type_id);
+        let token = self.internal_mint_token(token_id, receiver_id, pass_type.metadata);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        token
+    }
+
+    /// Redeem a mint pass of the predecessor for a warrior of the mint pool drop. The attached
+    /// deposit must cover the redeem price of the pass and the storage of the warrior, less
+    /// the storage freed by burning the pass.
+    #[payable]
+    pub fn redeem_pass(&mut self, pass_token_id: TokenId) -> Token {
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+        require!(
+            self.tokens.owner_by_id.get(&pass_token_id).as_ref() == Some(&owner_id),
+            "Predecessor must own the pass"
+        );
+        let pass_type = self
+            .pass_tokens
+            .get(&pass_token_id)
+            .and_then(|pass_type_id| self.pass_types.get(&pass_type_id))
+            .unwrap_or_else(|| env::panic_str("Token is not a mint pass"));
+        require!(
+            pass_type.redeemable,
+            "Passes of this type cannot be redeemed yet"
+        );
+        require!(
+            !self.used_passes.contains(&pass_token_id),
+            "Pass has already been used"
+        );
+        self.assert_active(&pass_token_id);
+
+        if pass_type.burn_on_redeem {
+            self.internal_burn(&pass_token_id, Some("redeem"));
+        } else {
+            self.used_passes.insert(&pass_token_id);
+        }
+        let token = self.internal_mint_warrior(owner_id);
+        self.internal_collect_mint_price(initial_storage_usage, pass_type.redeem_price.0);
+        token
+    }
+
+    /// Returns the type of a mint pass and whether it has been used.
+    pub fn nft_pass(&self, token_id: TokenId) -> Option<PassView> {
+        self.pass_tokens
+            .get(&token_id)
+            .map(|pass_type_id| PassView {
+                pass_type_id,
+                used: self.used_passes.contains(&token_id),
+            })
+    }
+
+    pub fn is_pass_used(&self, token_id: TokenId) -> bool {
+        self.used_passes.contains(&token_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{PassType, PassView};
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup(burn_on_redeem: bool) -> (VMContextBuilder, Contract, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        contract.set_mint_price(U128(1_000_000));
+        let pass_type_id = contract.add_pass_type(PassType {
+            metadata: sample_token_metadata(),
+            redeem_price: U128(0),
+            burn_on_redeem,
+            redeemable: true,
+        });
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let pass = contract.nft_mint_pass(pass_type_id, accounts(1)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        (context, contract, pass)
+    }
+
+    #[test]
+    fn test_redeem_marks_pass_used() {
+        let (_, mut contract, pass) = setup(false);
+        let token = contract.redeem_pass(pass.clone());
+        assert_eq!(token.owner_id, accounts(1));
+        assert!(contract.is_pass_used(pass.clone()));
+        assert_eq!(
+            contract.nft_pass(pass),
+            Some(PassView {
+                pass_type_id: 0,
+                used: true
+            })
+        );
+    }
+
+    #[test]
+    fn test_redeem_burns_pass() {
+        let (_, mut contract, pass) = setup(true);
+        contract.redeem_pass(pass.clone());
+        assert!(contract.nft_token(pass.clone()).is_none());
+        assert_eq!(contract.nft_pass(pass), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pass has already been used")]
+    fn test_redeem_used_pass() {
+        let (_, mut contract, pass) = setup(false);
+        contract.redeem_pass(pass.clone());
+        contract.redeem_pass(pass);
+    }
+}
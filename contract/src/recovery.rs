@@ -0,0 +1,420 @@
+//! Forced transfers for recovery, such as when a holder lost their keys or a court order
+//! requires it. An admin moves a token right away; a recovery account appointed by an admin
+//! schedules the move, which it can execute once the timelock has passed and admins can cancel
+//! until then. A scheduled move follows the token rather than its owner, so that a thief cannot
+//! dodge it by moving the token on. Forced transfers bypass approvals and the transfer policy,
+//! and admins can disable them for good, which anyone can verify with `get_recovery_config`.
+use crate::events::emit_contract_event;
+use crate::roles::Role;
+use crate::*;
+use near_sdk::assert_one_yocto;
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingAdminTransfer {
+    pub receiver_id: AccountId,
+    pub reason: String,
+    /// Block timestamp from which the transfer can be executed.
+    pub executable_at: U64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecoveryConfig {
+    pub recovery_account_id: Option<AccountId>,
+    /// Timelock of the transfers scheduled by the recovery account, in nanoseconds.
+    pub recovery_delay: U64,
+    pub admin_transfer_disabled: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct AdminTransfer<'a> {
+    token_id: &'a TokenId,
+    old_owner_id: &'a AccountId,
+    new_owner_id: &'a AccountId,
+    authorized_id: &'a AccountId,
+    reason: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct AdminTransferScheduled<'a> {
+    token_id: &'a TokenId,
+    receiver_id: &'a AccountId,
+    reason: &'a str,
+    executable_at: U64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct AdminTransferDisabled<'a> {
+    admin_id: &'a AccountId,
+}
+
+impl Contract {
+    fn assert_admin_transfer_enabled(&self) {
+        require!(
+            !self.admin_transfer_disabled,
+            "Admin transfers are permanently disabled"
+        );
+    }
+
+    fn assert_recovery_account(&self) {
+        require!(
+            self.recovery_account_id.as_ref() == Some(&env::predecessor_account_id()),
+            "Only the recovery account can call this method"
+        );
+    }
+
+    fn internal_admin_transfer(
+        &mut self,
+        token_id: &TokenId,
+        receiver_id: &AccountId,
+        reason: &str,
+    ) {
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+        require!(
+            owner_id != env::current_account_id(),
+            "Tokens held in the custody of the contract cannot be moved"
+        );
+        require!(
+            &owner_id != receiver_id,
+            "Current and next owner must differ"
+        );
+        self.assert_not_equipped(token_id);
+        self.assert_not_staked(token_id);
+        self.assert_not_blocked(receiver_id);
+
+        if let Some(by_id) = self.tokens.approvals_by_id.as_mut() {
+            by_id.remove(token_id);
+        }
+        self.approval_expirations.remove(token_id);
+        self.internal_clear_rental(token_id);
+        self.pending_admin_transfers.remove(token_id);
+        self.internal_move_token(token_id, &owner_id, receiver_id, "admin_transfer");
+        emit_contract_event(
+            "admin_transfer",
+            AdminTransfer {
+                token_id,
+                old_owner_id: &owner_id,
+                new_owner_id: receiver_id,
+                authorized_id: &env::predecessor_account_id(),
+                reason,
+            },
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Appoint the recovery account, or remove it with `None`, and set the timelock of the
+    /// transfers it schedules. Changing the account drops the transfers scheduled by the
+    /// previous one.
+    pub fn set_recovery_account(&mut self, account_id: Option<AccountId>, delay: U64) {
+        self.assert_role(Role::Admin);
+        self.assert_admin_transfer_enabled();
+        if account_id != self.recovery_account_id {
+            self.pending_admin_transfers.clear();
+        }
+        self.recovery_account_id = account_id;
+        self.recovery_delay = delay.0;
+    }
+
+    /// Move `token_id` to `receiver_id` without the approval of its owner. Called by an admin,
+    /// the token moves right away. Called by the recovery account, the move is
+    /// scheduled and executed with `execute_admin_transfer` once the timelock has passed.
+    ///
+    /// A scheduled move is kept when the token is transferred or sold until an admin cancels
+    /// it, so buyers should check `get_pending_admin_transfer` before buying a token.
+    #[payable]
+    pub fn admin_transfer(&mut self, token_id: TokenId, receiver_id: AccountId, reason: String) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_admin_transfer_enabled();
+        if self.account_has_role(Role::Admin, &env::predecessor_account_id()) {
+            assert_one_yocto();
+            self.internal_admin_transfer(&token_id, &receiver_id, &reason);
+            return;
+        }
+        self.assert_recovery_account();
+        require!(
+            self.tokens.owner_by_id.get(&token_id).is_some(),
+            "Token not found"
+        );
+        let executable_at = U64(env::block_timestamp().saturating_add(self.recovery_delay));
+        emit_contract_event(
+            "admin_transfer_scheduled",
+            AdminTransferScheduled {
+                token_id: &token_id,
+                receiver_id: &receiver_id,
+                reason: &reason,
+                executable_at,
+            },
+        );
+        self.pending_admin_transfers.insert(
+            &token_id,
+            &PendingAdminTransfer {
+                receiver_id,
+                reason,
+                executable_at,
+            },
+        );
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Execute a transfer scheduled by the recovery account once its timelock has passed.
+    #[payable]
+    pub fn execute_admin_transfer(&mut self, token_id: TokenId) {
+        assert_one_yocto();
+        self.assert_admin_transfer_enabled();
+        self.assert_recovery_account();
+        let pending = self
+            .pending_admin_transfers
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("No admin transfer is scheduled for the token"));
+        require!(
+            env::block_timestamp() >= pending.executable_at.0,
+            "Admin transfer is still timelocked"
+        );
+        self.internal_admin_transfer(&token_id, &pending.receiver_id, &pending.reason);
+    }
+
+    /// Cancel a transfer scheduled by the recovery account.
+    pub fn cancel_admin_transfer(&mut self, token_id: TokenId) {
+        self.assert_role(Role::Admin);
+        require!(
+            self.pending_admin_transfers.remove(&token_id).is_some(),
+            "No admin transfer is scheduled for the token"
+        );
+    }
+
+    /// Disable admin transfers for good, removing the recovery account. There is no way back.
+    #[payable]
+    pub fn disable_admin_transfer(&mut self) {
+        self.assert_role(Role::Admin);
+        assert_one_yocto();
+        self.admin_transfer_disabled = true;
+        self.recovery_account_id = None;
+        self.pending_admin_transfers.clear();
+        emit_contract_event(
+            "admin_transfer_disabled",
+            AdminTransferDisabled {
+                admin_id: &env::predecessor_account_id(),
+            },
+        );
+    }
+
+    pub fn get_recovery_config(&self) -> RecoveryConfig {
+        RecoveryConfig {
+            recovery_account_id: self.recovery_account_id.clone(),
+            recovery_delay: self.recovery_delay.into(),
+            admin_transfer_disabled: self.admin_transfer_disabled,
+        }
+    }
+
+    pub fn get_pending_admin_transfer(&self, token_id: TokenId) -> Option<PendingAdminTransfer> {
+        self.pending_admin_transfers.get(&token_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::staking::{RewardMode, StakingConfig};
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.set_recovery_account(Some(accounts(3)), U64(100));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        contract.nft_mint("0".to_string(), accounts(1), sample_token_metadata());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .build());
+        (context, contract)
+    }
+
+    fn schedule(context: &mut VMContextBuilder, contract: &mut Contract) {
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(1_000)
+            .build());
+        contract.admin_transfer("0".to_string(), accounts(2), "Court order".to_string());
+    }
+
+    #[test]
+    fn test_owner_admin_transfer() {
+        let (_, mut contract) = setup();
+        contract.admin_transfer("0".to_string(), accounts(2), "Lost keys".to_string());
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(2)
+        );
+        assert!(get_logs().last().unwrap().contains(
+            r#""event":"admin_transfer","data":[{"token_id":"0","old_owner_id":"bob","new_owner_id":"charlie","authorized_id":"alice","reason":"Lost keys"}]"#
+        ));
+    }
+
+    #[test]
+    fn test_recovery_timelock() {
+        let (mut context, mut contract) = setup();
+        schedule(&mut context, &mut contract);
+        assert_eq!(
+            contract
+                .get_pending_admin_transfer("0".to_string())
+                .unwrap()
+                .executable_at,
+            U64(1_100)
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .block_timestamp(1_100)
+            .build());
+        contract.execute_admin_transfer("0".to_string());
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(2)
+        );
+        assert!(contract
+            .get_pending_admin_transfer("0".to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn test_scheduled_transfer_survives_sale() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_recovery_account(Some(accounts(3)), U64(u64::MAX));
+        schedule(&mut context, &mut contract);
+        assert_eq!(
+            contract
+                .get_pending_admin_transfer("0".to_string())
+                .unwrap()
+                .executable_at,
+            U64(u64::MAX)
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(4), "0".to_string(), None, None);
+        assert!(contract
+            .get_pending_admin_transfer("0".to_string())
+            .is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Admin transfers are permanently disabled")]
+    fn test_disabled_admin_transfer() {
+        let (_, mut contract) = setup();
+        contract.disable_admin_transfer();
+        assert!(contract.get_recovery_config().admin_transfer_disabled);
+        contract.admin_transfer("0".to_string(), accounts(2), "Lost keys".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Admin transfer is still timelocked")]
+    fn test_execute_before_timelock() {
+        let (mut context, mut contract) = setup();
+        schedule(&mut context, &mut contract);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .block_timestamp(1_099)
+            .build());
+        contract.execute_admin_transfer("0".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "No admin transfer is scheduled for the token")]
+    fn test_cancel_admin_transfer() {
+        let (mut context, mut contract) = setup();
+        schedule(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.cancel_admin_transfer("0".to_string());
+        assert!(contract
+            .get_pending_admin_transfer("0".to_string())
+            .is_none());
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(1_100)
+            .build());
+        contract.execute_admin_transfer("0".to_string());
+    }
+
+    #[test]
+    fn test_new_recovery_account_drops_scheduled_transfers() {
+        let (mut context, mut contract) = setup();
+        schedule(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_recovery_account(Some(accounts(4)), U64(100));
+        assert!(contract
+            .get_pending_admin_transfer("0".to_string())
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Tokens held in the custody of the contract cannot be moved")]
+    fn test_admin_transfer_of_equipped_token() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        contract.nft_mint("1".to_string(), accounts(1), sample_token_metadata());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.equip("0".to_string(), "1".to_string());
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.admin_transfer("1".to_string(), accounts(2), "Lost keys".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is staked, unstake it first")]
+    fn test_admin_transfer_of_staked_token() {
+        let (mut context, mut contract) = setup();
+        contract.set_staking_config(Some(StakingConfig {
+            rates: vec![U128(10); 4],
+            reward_mode: RewardMode::Points,
+        }));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.stake("0".to_string());
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.admin_transfer("0".to_string(), accounts(2), "Lost keys".to_string());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::utils::refund_deposit_with_price;
+use crate::*;
+use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApproval;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, require, Balance};
+
+/// The account allowed to use a token until `expires`, distinct from its owner.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Rental {
+    pub user_id: AccountId,
+    pub expires: u64,
+    /// Account that listed the token and the rent it is owed, escrowed until the rental ends.
+    pub payment: Option<(AccountId, Balance)>,
+}
+
+/// Terms under which anyone can rent a token through `nft_rent`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RentalListing {
+    pub price: U128,
+    pub duration: U64,
+}
+
+impl Contract {
+    fn expect_owner_or_approved(&self, token_id: &TokenId) -> AccountId {
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .get(token_id)
+            .unwrap_or_else(|| env::panic_str("Token not found"));
+        let predecessor_id = env::predecessor_account_id();
+        self.assert_not_blocked(&predecessor_id);
+        require!(
+            predecessor_id == owner_id
+                || self.nft_is_approved(token_id.clone(), predecessor_id, None),
+            "Predecessor must be token owner or approved"
+        );
+        owner_id
+    }
+
+    /// Removes an expired rental of `token_id`, releasing its escrowed rent to the lessor.
+    fn internal_settle_rental(&mut self, token_id: &TokenId) {
+        if let Some(rental) = self.rentals.get(token_id) {
+            if rental.expires <= env::block_timestamp() {
+                self.rentals.remove(token_id);
+                if let Some((lessor_id, amount)) = rental.payment {
+                    Promise::new(lessor_id).transfer(amount);
+                }
+            }
+        }
+    }
+
+    /// Clears the rental and the rental listing of `token_id` when it changes owner.
+    /// The escrowed rent of a rental still running is returned to its user.
+    pub(crate) fn internal_clear_rental(&mut self, token_id: &TokenId) {
+        self.internal_settle_rental(token_id);
+        if let Some(rental) = self.rentals.remove(token_id) {
+            if let Some((_, amount)) = rental.payment {
+                Promise::new(rental.user_id).transfer(amount);
+            }
+        }
+        self.rental_listings.remove(token_id);
+    }
+
+    fn internal_set_user(
+        &mut self,
+        token_id: &TokenId,
+        user_id: AccountId,
+        expires: u64,
+        payment: Option<(AccountId, Balance)>,
+    ) {
+        require!(
+            expires > env::block_timestamp(),
+            "Expiration must be in the future"
+        );
+        self.assert_not_blocked(&user_id);
+        self.internal_settle_rental(token_id);
+        require!(
+            self.rentals.get(token_id).is_none(),
+            "Token is already rented"
+        );
+        self.rentals.insert(
+            token_id,
+            &Rental {
+                user_id,
+                expires,
+                payment,
+            },
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Let `user_id` use `token_id` until the `expires` block timestamp (in nanoseconds).
+    /// Callable by the token owner or an approved account while the token is not rented.
+    #[payable]
+    pub fn nft_set_user(&mut self, token_id: TokenId, user_id: AccountId, expires: U64) {
+        let initial_storage_usage = env::storage_usage();
+        self.expect_owner_or_approved(&token_id);
+        self.internal_set_user(&token_id, user_id, expires.0, None);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Returns the current user of `token_id`, or `None` if it is not rented or the rental
+    /// has expired.
+    pub fn nft_user_of(&self, token_id: TokenId) -> Option<AccountId> {
+        self.rentals
+            .get(&token_id)
+            .filter(|rental| rental.expires > env::block_timestamp())
+            .map(|rental| rental.user_id)
+    }
+
+    /// Returns the block timestamp at which the current rental of `token_id` ends.
+    pub fn nft_user_expires(&self, token_id: TokenId) -> Option<U64> {
+        self.rentals
+            .get(&token_id)
+            .filter(|rental| rental.expires > env::block_timestamp())
+            .map(|rental| rental.expires.into())
+    }
+
+    /// List `token_id` so that anyone can rent it for `duration` nanoseconds by paying `price`.
+    #[payable]
+    pub fn nft_list_for_rent(&mut self, token_id: TokenId, price: U128, duration: U64) {
+        let initial_storage_usage = env::storage_usage();
+        self.expect_owner_or_approved(&token_id);
+        require!(duration.0 > 0, "Duration must be positive");
+        self.rental_listings
+            .insert(&token_id, &RentalListing { price, duration });
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    #[payable]
+    pub fn nft_delist_from_rent(&mut self, token_id: TokenId) {
+        assert_one_yocto();
+        self.expect_owner_or_approved(&token_id);
+        self.rental_listings.remove(&token_id);
+    }
+
+    pub fn nft_rental_listing(&self, token_id: TokenId) -> Option<RentalListing> {
+        self.rental_listings.get(&token_id)
+    }
+
+    /// Rent a listed token at the `price` and for the `duration` the renter expects, which must
+    /// match the listing. The attached deposit must cover the price and the storage of the
+    /// rental; the price is escrowed until the rental ends, then paid to the account which
+    /// owned the token when it was rented.
+    #[payable]
+    pub fn nft_rent(&mut self, token_id: TokenId, price: U128, duration: U64) {
+        let initial_storage_usage = env::storage_usage();
+        let listing = self
+            .rental_listings
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token is not listed for rent"));
+        require!(
+            listing.price == price && listing.duration == duration,
+            "Rental listing does not match the expected price and duration"
+        );
+        let owner_id = self.tokens.owner_by_id.get(&token_id).unwrap();
+        let user_id = env::predecessor_account_id();
+        require!(user_id != owner_id, "Owner cannot rent their own token");
+        self.assert_not_blocked(&owner_id);
+
+        let price: Balance = listing.price.into();
+        let expires = env::block_timestamp() + listing.duration.0;
+        self.internal_set_user(&token_id, user_id, expires, Some((owner_id, price)));
+
+        refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            price,
+        );
+    }
+
+    /// Pay out the escrowed rent of a rental that has ended.
+    pub fn nft_claim_rent(&mut self, token_id: TokenId) {
+        let rental = self
+            .rentals
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token is not rented"));
+        require!(
+            rental.expires <= env::block_timestamp(),
+            "Rental has not ended yet"
+        );
+        self.internal_settle_rental(&token_id);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_STORAGE_COST};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const RENTAL_STORAGE_COST: u128 = 10_000_000_000_000_000_000_000;
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.nft_mint("0".to_string(), accounts(0), sample_token_metadata());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_set_user_expires() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RENTAL_STORAGE_COST)
+            .block_timestamp(100)
+            .build());
+        contract.nft_set_user("0".to_string(), accounts(1), U64(200));
+        assert_eq!(contract.nft_user_of("0".to_string()), Some(accounts(1)));
+        assert_eq!(contract.nft_user_expires("0".to_string()), Some(U64(200)));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(200)
+            .build());
+        assert_eq!(contract.nft_user_of("0".to_string()), None);
+    }
+
+    #[test]
+    fn test_transfer_clears_user() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RENTAL_STORAGE_COST)
+            .block_timestamp(100)
+            .build());
+        contract.nft_set_user("0".to_string(), accounts(1), U64(200));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .build());
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+        assert_eq!(contract.nft_user_of("0".to_string()), None);
+    }
+
+    #[test]
+    fn test_rent_listed_token() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RENTAL_STORAGE_COST)
+            .block_timestamp(100)
+            .build());
+        contract.nft_list_for_rent("0".to_string(), U128(1_000), U64(50));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RENTAL_STORAGE_COST + 1_000)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_rent("0".to_string(), U128(1_000), U64(50));
+        assert_eq!(contract.nft_user_of("0".to_string()), Some(accounts(1)));
+        assert_eq!(contract.nft_user_expires("0".to_string()), Some(U64(150)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is already rented")]
+    fn test_cannot_override_active_rental() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RENTAL_STORAGE_COST)
+            .block_timestamp(100)
+            .build());
+        contract.nft_set_user("0".to_string(), accounts(1), U64(200));
+        contract.nft_set_user("0".to_string(), accounts(2), U64(300));
+    }
+
+    #[test]
+    #[should_panic(expected = "Rental listing does not match the expected price and duration")]
+    fn test_rent_after_relisting() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RENTAL_STORAGE_COST)
+            .build());
+        contract.nft_list_for_rent("0".to_string(), U128(1_000), U64(50));
+        contract.nft_list_for_rent("0".to_string(), U128(5_000), U64(50));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RENTAL_STORAGE_COST + 5_000)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_rent("0".to_string(), U128(1_000), U64(50));
+    }
+}
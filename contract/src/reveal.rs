@@ -0,0 +1,262 @@
+use crate::roles::Role;
+use crate::*;
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::require;
+use near_sdk::serde::Serialize;
+use std::convert::TryInto;
+
+/// A blind drop: tokens are minted with `placeholder` metadata until the drop is revealed.
+///
+/// The final metadata of the `size` items of the drop is listed in a manifest, published at
+/// `<base_uri>/manifest.json` on reveal. Its sha256, the manifest hash, is committed up front
+/// along with a provenance seed as `sha256(manifest_hash ++ provenance_seed)`, so the art cannot
+/// be swapped after minting started. On reveal, the seed and the randomness of the reveal block
+/// pick the offset at which tokens are mapped onto items: token `i` gets item
+/// `(i + offset) % size`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BlindDrop {
+    pub placeholder: TokenMetadata,
+    pub commitment: Vec<u8>,
+    pub size: u64,
+    pub revealed: Option<Reveal>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Reveal {
+    pub base_uri: String,
+    pub manifest_hash: Vec<u8>,
+    pub provenance_seed: String,
+    pub offset: u64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RevealView {
+    pub commitment: Base64VecU8,
+    pub size: U64,
+    pub base_uri: Option<String>,
+    pub manifest_hash: Option<Base64VecU8>,
+    pub provenance_seed: Option<String>,
+    pub offset: Option<U64>,
+}
+
+pub(crate) fn reveal_commitment(manifest_hash: &[u8], provenance_seed: &str) -> Vec<u8> {
+    env::sha256(&[manifest_hash, provenance_seed.as_bytes()].concat())
+}
+
+impl Contract {
+    /// Placeholder metadata to mint with while a blind drop is waiting to be revealed.
+    pub(crate) fn blind_drop_placeholder(&self) -> Option<TokenMetadata> {
+        self.blind_drop
+            .get()
+            .filter(|blind_drop| blind_drop.revealed.is_none())
+            .map(|blind_drop| blind_drop.placeholder)
+    }
+
+    /// Swaps the placeholder metadata of a blind-minted token for its item of the manifest once
+    /// the drop has been revealed.
+    pub(crate) fn internal_reveal_metadata(
+        &self,
+        token_id: &TokenId,
+        metadata: TokenMetadata,
+    ) -> TokenMetadata {
+        if !self.blind_tokens.contains(token_id) {
+            return metadata;
+        }
+        let blind_drop = match self.blind_drop.get() {
+            Some(blind_drop) => blind_drop,
+            None => return metadata,
+        };
+        match (blind_drop.revealed, token_id.parse::<u64>()) {
+            (Some(reveal), Ok(index)) => {
+                let item =
+                    (u128::from(index) + u128::from(reveal.offset)) % u128::from(blind_drop.size);
+                TokenMetadata {
+                    media: Some(format!("{}/{}.png", reveal.base_uri, item)),
+                    media_hash: None,
+                    reference: Some(format!("{}/manifest.json", reveal.base_uri)),
+                    reference_hash: Some(reveal.manifest_hash.into()),
+                    ..metadata
+                }
+            }
+            _ => metadata,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Start a blind drop of `size` items: `nft_mint_default` mints `placeholder` metadata
+    /// until `reveal` is called with the manifest hash and provenance seed hashing to
+    /// `commitment`.
+    pub fn start_blind_drop(
+        &mut self,
+        placeholder: TokenMetadata,
+        commitment: Base64VecU8,
+        size: U64,
+    ) {
+        self.assert_role(Role::Minter);
+        require!(
+            self.blind_drop.get().is_none(),
+            "Blind drop already started"
+        );
+        require!(commitment.0.len() == 32, "Commitment has to be 32 bytes");
+        require!(size.0 > 0, "Blind drop must have items");
+        self.blind_drop.set(&BlindDrop {
+            placeholder,
+            commitment: commitment.into(),
+            size: size.0,
+            revealed: None,
+        });
+    }
+
+    /// Reveal the final metadata of every token minted during the blind drop, given the
+    /// `base_uri` the manifest and its items are hosted at.
+    pub fn reveal(
+        &mut self,
+        base_uri: String,
+        manifest_hash: Base64VecU8,
+        provenance_seed: String,
+    ) {
+        self.assert_role(Role::MetadataUpdater);
+        let mut blind_drop = self
+            .blind_drop
+            .get()
+            .unwrap_or_else(|| env::panic_str("No blind drop started"));
+        require!(blind_drop.revealed.is_none(), "Already revealed");
+        require!(
+            reveal_commitment(&manifest_hash.0, &provenance_seed) == blind_drop.commitment,
+            "Revealed values do not match the commitment"
+        );
+        let random = env::sha256(&[provenance_seed.as_bytes(), &env::random_seed()].concat());
+        let offset = u64::from_le_bytes(random[..8].try_into().unwrap()) % blind_drop.size;
+        blind_drop.revealed = Some(Reveal {
+            base_uri,
+            manifest_hash: manifest_hash.into(),
+            provenance_seed,
+            offset,
+        });
+        self.blind_drop.set(&blind_drop);
+    }
+
+    pub fn get_reveal(&self) -> Option<RevealView> {
+        self.blind_drop.get().map(|blind_drop| {
+            let reveal = blind_drop.revealed;
+            RevealView {
+                commitment: blind_drop.commitment.into(),
+                size: blind_drop.size.into(),
+                base_uri: reveal.as_ref().map(|reveal| reveal.base_uri.clone()),
+                manifest_hash: reveal
+                    .as_ref()
+                    .map(|reveal| reveal.manifest_hash.clone().into()),
+                provenance_seed: reveal.as_ref().map(|reveal| reveal.provenance_seed.clone()),
+                offset: reveal.map(|reveal| reveal.offset.into()),
+            }
+        })
+    }
+
+    /// Check whether `manifest_hash` and `provenance_seed` match the commitment of the blind
+    /// drop.
+    pub fn verify_reveal(&self, manifest_hash: Base64VecU8, provenance_seed: String) -> bool {
+        self.blind_drop.get().map_or(false, |blind_drop| {
+            reveal_commitment(&manifest_hash.0, &provenance_seed) == blind_drop.commitment
+        })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::reveal_commitment;
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
+    use near_sdk::json_types::{Base64VecU8, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const BASE_URI: &str = "https://example.com/warriors";
+    const SEED: &str = "42";
+    const SIZE: u64 = 4;
+
+    fn manifest_hash() -> Base64VecU8 {
+        Base64VecU8(env::sha256(b"[\"final metadata\"]"))
+    }
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(SIZE);
+        contract.start_blind_drop(
+            sample_token_metadata(),
+            Base64VecU8(reveal_commitment(&manifest_hash().0, SEED)),
+            U64(SIZE),
+        );
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let token_id = contract.nft_mint_default(accounts(1)).token_id;
+        (context, contract, token_id)
+    }
+
+    #[test]
+    fn test_mint_placeholder_then_reveal() {
+        let (mut context, mut contract, token_id) = setup();
+        let metadata = contract
+            .nft_token(token_id.clone())
+            .unwrap()
+            .metadata
+            .unwrap();
+        assert_eq!(metadata.title, sample_token_metadata().title);
+        assert_eq!(metadata.media, None);
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(0))
+            .build());
+        assert!(contract.verify_reveal(manifest_hash(), SEED.to_string()));
+        contract.reveal(BASE_URI.to_string(), manifest_hash(), SEED.to_string());
+
+        let reveal = contract.get_reveal().unwrap();
+        assert_eq!(reveal.provenance_seed, Some(SEED.to_string()));
+        let item = (token_id.parse::<u64>().unwrap() + reveal.offset.unwrap().0) % SIZE;
+        let metadata = contract.nft_token(token_id).unwrap().metadata.unwrap();
+        assert_eq!(metadata.media, Some(format!("{}/{}.png", BASE_URI, item)));
+        let metadata = contract.nft_tokens(None, None)[0].metadata.clone().unwrap();
+        assert_eq!(
+            metadata.reference,
+            Some(format!("{}/manifest.json", BASE_URI))
+        );
+        assert_eq!(metadata.reference_hash, Some(manifest_hash()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Revealed values do not match the commitment")]
+    fn test_reveal_wrong_seed() {
+        let (mut context, mut contract, _) = setup();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.reveal(BASE_URI.to_string(), manifest_hash(), "43".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Revealed values do not match the commitment")]
+    fn test_reveal_other_manifest() {
+        let (mut context, mut contract, _) = setup();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.reveal(
+            BASE_URI.to_string(),
+            Base64VecU8(env::sha256(b"[\"swapped metadata\"]")),
+            SEED.to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires the MetadataUpdater role")]
+    fn test_reveal_not_owner() {
+        let (_, mut contract, _) = setup();
+        contract.reveal(BASE_URI.to_string(), manifest_hash(), SEED.to_string());
+    }
+}
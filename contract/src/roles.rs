@@ -0,0 +1,529 @@
+//! Roles of privileged accounts. Every privileged method requires one role, and accounts can
+//! hold several. Admins grant and revoke roles; the account the contract is initialized with
+//! starts with every role. The only exceptions are the methods of the accounts admins appoint
+//! for a single task: verifiers, the game server and the recovery account.
+use crate::events::emit_contract_event;
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    /// Manages roles and the policies of the contract.
+    Admin,
+    /// Mints tokens and items, and sets up drops, passes and tickets.
+    Minter,
+    /// Updates the metadata of minted tokens.
+    MetadataUpdater,
+    /// Pauses and unpauses mints and transfers.
+    Pauser,
+    /// Sets prices, fees and rewards, and funds distributions.
+    Treasurer,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [
+        Role::Admin,
+        Role::Minter,
+        Role::MetadataUpdater,
+        Role::Pauser,
+        Role::Treasurer,
+    ];
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct RoleChange<'a> {
+    role: Role,
+    account_id: &'a AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct PauseChange<'a> {
+    pauser_id: &'a AccountId,
+}
+
+impl Contract {
+    pub(crate) fn internal_grant_role(&mut self, role: Role, account_id: &AccountId) {
+        let mut members = self.roles.get(&role).unwrap_or_default();
+        if members.insert(account_id.clone()) {
+            self.roles.insert(&role, &members);
+            emit_contract_event("role_granted", RoleChange { role, account_id });
+        }
+    }
+
+    pub(crate) fn account_has_role(&self, role: Role, account_id: &AccountId) -> bool {
+        self.roles
+            .get(&role)
+            .map_or(false, |members| members.contains(account_id))
+    }
+
+    pub(crate) fn assert_account_role(&self, role: Role, account_id: &AccountId) {
+        require!(
+            self.account_has_role(role, account_id),
+            format!("Requires the {:?} role", role)
+        );
+    }
+
+    /// Panics unless the predecessor holds `role`.
+    pub(crate) fn assert_role(&self, role: Role) {
+        self.assert_account_role(role, &env::predecessor_account_id());
+    }
+
+    pub(crate) fn assert_not_paused(&self) {
+        require!(!self.paused, "Contract is paused");
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn grant_role(&mut self, role: Role, account_id: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Admin);
+        self.internal_grant_role(role, &account_id);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Revoke `role` from `account_id`. The last admin cannot be revoked.
+    pub fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+        self.assert_role(Role::Admin);
+        let mut members = self.roles.get(&role).unwrap_or_default();
+        if !members.remove(&account_id) {
+            return;
+        }
+        require!(
+            role != Role::Admin || !members.is_empty(),
+            "Cannot revoke the last admin"
+        );
+        self.roles.insert(&role, &members);
+        emit_contract_event(
+            "role_revoked",
+            RoleChange {
+                role,
+                account_id: &account_id,
+            },
+        );
+    }
+
+    pub fn has_role(&self, role: Role, account_id: AccountId) -> bool {
+        self.account_has_role(role, &account_id)
+    }
+
+    pub fn get_role_members(&self, role: Role) -> Vec<AccountId> {
+        let mut members: Vec<_> = self
+            .roles
+            .get(&role)
+            .map_or_else(Vec::new, |members: HashSet<AccountId>| {
+                members.into_iter().collect()
+            });
+        members.sort();
+        members
+    }
+
+    pub fn pause(&mut self) {
+        self.assert_role(Role::Pauser);
+        if !self.paused {
+            self.paused = true;
+            emit_contract_event(
+                "paused",
+                PauseChange {
+                    pauser_id: &env::predecessor_account_id(),
+                },
+            );
+        }
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_role(Role::Pauser);
+        if self.paused {
+            self.paused = false;
+            emit_contract_event(
+                "unpaused",
+                PauseChange {
+                    pauser_id: &env::predecessor_account_id(),
+                },
+            );
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::Role;
+    use crate::breeding::BreedingConfig;
+    use crate::crafting::Recipe;
+    use crate::governance::ProposalAction;
+    use crate::passes::PassType;
+    use crate::staking::{RewardMode, StakingConfig};
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::transfer_policy::TransferPolicy;
+    use crate::*;
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{Base64VecU8, U128, U64};
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+        let contract = Contract::new_default_meta(accounts(0));
+        (context, contract)
+    }
+
+    #[test]
+    fn test_grant_and_revoke() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.attached_deposit(MINT_DEPOSIT).build());
+        contract.grant_role(Role::Minter, accounts(1));
+        assert!(contract.has_role(Role::Minter, accounts(1)));
+        assert_eq!(
+            contract.get_role_members(Role::Minter),
+            vec![accounts(0), accounts(1)]
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_mint("0".to_string(), accounts(2), sample_token_metadata());
+        assert!(contract.nft_token("0".to_string()).is_some());
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.revoke_role(Role::Minter, accounts(1));
+        assert!(!contract.has_role(Role::Minter, accounts(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn test_pause() {
+        let (mut context, mut contract) = setup();
+        contract.pause();
+        assert!(contract.is_paused());
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"hellomint","version":"1.0.0","event":"paused","data":[{"pauser_id":"alice"}]}"#
+        );
+        testing_env!(context.attached_deposit(MINT_DEPOSIT).build());
+        contract.nft_mint("0".to_string(), accounts(2), sample_token_metadata());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot revoke the last admin")]
+    fn test_revoke_last_admin() {
+        let (_, mut contract) = setup();
+        contract.revoke_role(Role::Admin, accounts(0));
+    }
+
+    /// Calls every privileged method from an account without roles, nor any of the accounts
+    /// appointed as verifier, game server or recovery account.
+    #[test]
+    fn test_unauthorized_access() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+
+        type Call = Box<dyn Fn(&mut Contract)>;
+        let token = || "0".to_string();
+        let calls: Vec<(&str, Role, Call)> = vec![
+            (
+                "grant_role",
+                Role::Admin,
+                Box::new(|c| c.grant_role(Role::Minter, accounts(1))),
+            ),
+            (
+                "revoke_role",
+                Role::Admin,
+                Box::new(|c| c.revoke_role(Role::Admin, accounts(0))),
+            ),
+            ("pause", Role::Pauser, Box::new(|c| c.pause())),
+            ("unpause", Role::Pauser, Box::new(|c| c.unpause())),
+            (
+                "nft_mint",
+                Role::Minter,
+                Box::new(move |c| {
+                    c.nft_mint(token(), accounts(1), sample_token_metadata());
+                }),
+            ),
+            (
+                "set_mint_price",
+                Role::Treasurer,
+                Box::new(|c| c.set_mint_price(U128(1))),
+            ),
+            (
+                "init_mint_pool",
+                Role::Minter,
+                Box::new(|c| c.init_mint_pool(10)),
+            ),
+            (
+                "start_blind_drop",
+                Role::Minter,
+                Box::new(|c| {
+                    c.start_blind_drop(sample_token_metadata(), Base64VecU8(vec![0; 32]), U64(1))
+                }),
+            ),
+            (
+                "reveal",
+                Role::MetadataUpdater,
+                Box::new(|c| {
+                    c.reveal(
+                        "https://example.com".to_string(),
+                        Base64VecU8(vec![0; 32]),
+                        "seed".to_string(),
+                    )
+                }),
+            ),
+            (
+                "set_game_server",
+                Role::Admin,
+                Box::new(|c| c.set_game_server(None)),
+            ),
+            (
+                "add_recipe",
+                Role::Admin,
+                Box::new(|c| {
+                    c.add_recipe(Recipe {
+                        inputs: vec![],
+                        output: sample_token_metadata(),
+                    });
+                }),
+            ),
+            (
+                "remove_recipe",
+                Role::Admin,
+                Box::new(|c| c.remove_recipe(0)),
+            ),
+            (
+                "set_breeding_config",
+                Role::Treasurer,
+                Box::new(|c| {
+                    c.set_breeding_config(Some(BreedingConfig {
+                        fee: U128(0),
+                        cooldown: U64(0),
+                        max_breed_count: 1,
+                    }))
+                }),
+            ),
+            (
+                "set_staking_config",
+                Role::Treasurer,
+                Box::new(|c| {
+                    c.set_staking_config(Some(StakingConfig {
+                        rates: vec![U128(0); 4],
+                        reward_mode: RewardMode::Points,
+                    }))
+                }),
+            ),
+            (
+                "bind_proposal_action",
+                Role::Admin,
+                Box::new(|c| c.bind_proposal_action(0, 0, ProposalAction::SetMintPrice(U128(1)))),
+            ),
+            (
+                "create_distribution",
+                Role::Treasurer,
+                Box::new(|c| {
+                    c.create_distribution();
+                }),
+            ),
+            (
+                "ft_on_transfer",
+                Role::Treasurer,
+                Box::new(|c| {
+                    let _ = c.ft_on_transfer(accounts(1), U128(1), String::new());
+                }),
+            ),
+            (
+                "add_pass_type",
+                Role::Minter,
+                Box::new(|c| {
+                    c.add_pass_type(PassType {
+                        metadata: sample_token_metadata(),
+                        redeem_price: U128(0),
+                        burn_on_redeem: true,
+                        redeemable: false,
+                    });
+                }),
+            ),
+            (
+                "set_pass_redeemable",
+                Role::Minter,
+                Box::new(|c| c.set_pass_redeemable(0, true)),
+            ),
+            (
+                "nft_mint_pass",
+                Role::Minter,
+                Box::new(|c| {
+                    c.nft_mint_pass(0, accounts(1));
+                }),
+            ),
+            (
+                "mt_create",
+                Role::Minter,
+                Box::new(move |c| c.mt_create(token(), sample_token_metadata())),
+            ),
+            (
+                "mt_mint",
+                Role::Minter,
+                Box::new(move |c| c.mt_mint(token(), accounts(1), U128(1), None)),
+            ),
+            (
+                "burn_expired_tokens",
+                Role::Admin,
+                Box::new(move |c| {
+                    c.burn_expired_tokens(vec![token()]);
+                }),
+            ),
+            (
+                "add_verifier",
+                Role::Admin,
+                Box::new(|c| c.add_verifier(accounts(1))),
+            ),
+            (
+                "remove_verifier",
+                Role::Admin,
+                Box::new(|c| c.remove_verifier(accounts(1))),
+            ),
+            (
+                "create_event_series",
+                Role::Minter,
+                Box::new(|c| c.create_event_series("gig".to_string(), "Gig".to_string(), true)),
+            ),
+            (
+                "add_tickets",
+                Role::Minter,
+                Box::new(move |c| c.add_tickets("gig".to_string(), vec![token()])),
+            ),
+            (
+                "set_transfer_policy",
+                Role::Admin,
+                Box::new(|c| c.set_transfer_policy(TransferPolicy::default())),
+            ),
+            (
+                "add_listed_receivers",
+                Role::Admin,
+                Box::new(|c| c.add_listed_receivers(vec![accounts(1)])),
+            ),
+            (
+                "remove_listed_receivers",
+                Role::Admin,
+                Box::new(|c| c.remove_listed_receivers(vec![accounts(1)])),
+            ),
+            (
+                "lock_transfers",
+                Role::Minter,
+                Box::new(move |c| c.lock_transfers(token(), None)),
+            ),
+            (
+                "unlock_transfers",
+                Role::Minter,
+                Box::new(move |c| c.unlock_transfers(token())),
+            ),
+            (
+                "add_to_blocklist",
+                Role::Admin,
+                Box::new(|c| c.add_to_blocklist(accounts(2))),
+            ),
+            (
+                "remove_from_blocklist",
+                Role::Admin,
+                Box::new(|c| c.remove_from_blocklist(accounts(2))),
+            ),
+            (
+                "set_recovery_account",
+                Role::Admin,
+                Box::new(|c| c.set_recovery_account(Some(accounts(1)), U64(0))),
+            ),
+            (
+                "cancel_admin_transfer",
+                Role::Admin,
+                Box::new(move |c| c.cancel_admin_transfer(token())),
+            ),
+            (
+                "disable_admin_transfer",
+                Role::Admin,
+                Box::new(|c| c.disable_admin_transfer()),
+            ),
+            (
+                "sweep_distribution",
+                Role::Treasurer,
+                Box::new(|c| {
+                    c.sweep_distribution(0);
+                }),
+            ),
+            (
+                "add_distribution_ft",
+                Role::Treasurer,
+                Box::new(|c| c.add_distribution_ft(accounts(3))),
+            ),
+            (
+                "remove_distribution_ft",
+                Role::Treasurer,
+                Box::new(|c| c.remove_distribution_ft(accounts(3))),
+            ),
+            (
+                "add_equipment_contract",
+                Role::Admin,
+                Box::new(|c| c.add_equipment_contract(accounts(3))),
+            ),
+            (
+                "remove_equipment_contract",
+                Role::Admin,
+                Box::new(|c| c.remove_equipment_contract(accounts(3))),
+            ),
+        ];
+        let appointed_calls: Vec<(&str, &str, Call)> = vec![
+            (
+                "check_in",
+                "Only verifiers can check tickets in",
+                Box::new(move |c| {
+                    c.check_in(token(), U64(0), Base64VecU8(vec![0; 64]));
+                }),
+            ),
+            (
+                "gain_xp",
+                "Only the game server can award experience",
+                Box::new(move |c| {
+                    c.gain_xp(token(), U64(1));
+                }),
+            ),
+            (
+                "admin_transfer",
+                "Only the recovery account can call this method",
+                Box::new(move |c| c.admin_transfer(token(), accounts(2), "Lost keys".to_string())),
+            ),
+            (
+                "execute_admin_transfer",
+                "Only the recovery account can call this method",
+                Box::new(move |c| c.execute_admin_transfer(token())),
+            ),
+        ];
+
+        let expected_calls = calls
+            .into_iter()
+            .map(|(method, role, call)| (method, format!("Requires the {:?} role", role), call))
+            .chain(
+                appointed_calls
+                    .into_iter()
+                    .map(|(method, message, call)| (method, message.to_string(), call)),
+            );
+        for (method, expected, call) in expected_calls {
+            let message = catch_unwind(AssertUnwindSafe(|| call(&mut contract)))
+                .err()
+                .and_then(|panic| panic.downcast_ref::<String>().cloned());
+            assert_eq!(message, Some(expected), "{} must be refused", method);
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use crate::roles::Role;
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{assert_one_yocto, Balance};
+
+/// Number of rarity tiers, see `WarriorTraits::rarity`.
+pub(crate) const RARITY_TIERS: usize = 4;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum RewardMode {
+    /// Rewards are paid in yoctoNEAR out of the reward pool.
+    Near,
+    /// Rewards are credited as points tracked by the contract.
+    Points,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakingConfig {
+    /// Reward per second of block time for a staked warrior, by rarity tier from common to
+    /// legendary.
+    pub rates: Vec<U128>,
+    pub reward_mode: RewardMode,
+}
+
+/// Rewards accrued by a warrior of a rarity tier staked since the start, in reward units. Each
+/// reward mode has its own index, and the indexes only move while staking is enabled, for every
+/// whole second at the rates of the time, so config changes apply from then on. They saturate
+/// rather than overflow, so that stakes can always be unstaked.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardIndex {
+    pub near: U128,
+    pub points: U128,
+}
+
+impl Default for RewardIndex {
+    fn default() -> Self {
+        Self {
+            near: U128(0),
+            points: U128(0),
+        }
+    }
+}
+
+/// Rewards paid out by a claim, or pending: yoctoNEAR from the reward pool and reward points.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Rewards {
+    pub near: U128,
+    pub points: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Stake {
+    pub owner_id: AccountId,
+    /// Rarity tier of the warrior when it was staked, at whose rates it earns.
+    pub rarity: u8,
+    /// Reward index of its tier up to which the rewards of the warrior were paid.
+    pub reward_index: RewardIndex,
+}
+
+impl Contract {
+    fn expect_staking_config(&self) -> StakingConfig {
+        self.staking_config
+            .clone()
+            .unwrap_or_else(|| env::panic_str("Staking is not enabled"))
+    }
+
+    fn rarity(&self, token_id: &TokenId) -> usize {
+        self.token_traits
+            .get(token_id)
+            .map_or(0, |traits| traits.rarity())
+    }
+
+    /// Whole seconds since the reward indexes were last recorded.
+    fn reward_seconds_elapsed(&self) -> u64 {
+        env::block_timestamp().saturating_sub(self.reward_indexes_updated_at) / NANOS_PER_SECOND
+    }
+
+    /// Reward indexes of every rarity tier as of now.
+    fn current_reward_indexes(&self) -> Vec<RewardIndex> {
+        let mut indexes = self.reward_indexes.clone();
+        if let Some(config) = &self.staking_config {
+            let elapsed = u128::from(self.reward_seconds_elapsed());
+            for (index, rate) in indexes.iter_mut().zip(&config.rates) {
+                let accrued = match config.reward_mode {
+                    RewardMode::Near => &mut index.near,
+                    RewardMode::Points => &mut index.points,
+                };
+                accrued.0 = accrued.0.saturating_add(rate.0.saturating_mul(elapsed));
+            }
+        }
+        indexes
+    }
+
+    /// Records the reward indexes as of now, before the staking config changes.
+    fn internal_checkpoint_reward_indexes(&mut self) {
+        let elapsed = self.reward_seconds_elapsed();
+        self.reward_indexes = self.current_reward_indexes();
+        // the fraction of a second left over keeps accruing
+        self.reward_indexes_updated_at += elapsed * NANOS_PER_SECOND;
+    }
+
+    /// Returns the pending rewards of a stake in yoctoNEAR and in points.
+    fn pending_reward(stake: &Stake, indexes: &[RewardIndex]) -> (u128, u128) {
+        let index = indexes[usize::from(stake.rarity)];
+        (
+            index.near.0 - stake.reward_index.near.0,
+            index.points.0 - stake.reward_index.points.0,
+        )
+    }
+
+    /// Pays out the pending rewards of the given staked warriors of `owner_id`. Returns the
+    /// yoctoNEAR paid and the points credited.
+    fn internal_claim_rewards(&mut self, owner_id: &AccountId, token_ids: &[TokenId]) -> Rewards {
+        let indexes = self.current_reward_indexes();
+        let (mut near, mut points): (u128, u128) = (0, 0);
+        for token_id in token_ids {
+            let mut stake = self.stakes.get(token_id).unwrap();
+            let (stake_near, stake_points) = Self::pending_reward(&stake, &indexes);
+            stake.reward_index = indexes[usize::from(stake.rarity)];
+            self.stakes.insert(token_id, &stake);
+            near = near.saturating_add(stake_near);
+            points = points.saturating_add(stake_points);
+        }
+        if points > 0 {
+            let credited = self.reward_points.get(owner_id).unwrap_or(0);
+            self.reward_points
+                .insert(owner_id, &credited.saturating_add(points));
+        }
+        Rewards {
+            near: self.internal_pay_near_rewards(owner_id, near).into(),
+            points: points.into(),
+        }
+    }
+
+    /// Pays `reward` and the rewards `owner_id` is still owed out of the reward pool. What the
+    /// pool cannot cover stays owed. Returns the amount paid.
+    fn internal_pay_near_rewards(&mut self, owner_id: &AccountId, reward: Balance) -> Balance {
+        let due = self
+            .owed_rewards
+            .get(owner_id)
+            .unwrap_or(0)
+            .saturating_add(reward);
+        let paid = due.min(self.reward_pool);
+        if due > paid {
+            self.owed_rewards.insert(owner_id, &(due - paid));
+        } else {
+            self.owed_rewards.remove(owner_id);
+        }
+        if paid > 0 {
+            self.reward_pool -= paid;
+            Promise::new(owner_id.clone()).transfer(paid);
+        }
+        paid
+    }
+
+    pub(crate) fn assert_not_staked(&self, token_id: &TokenId) {
+        require!(
+            self.stakes.get(token_id).is_none(),
+            "Token is staked, unstake it first"
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Enable staking with a reward rate per rarity tier, or disable it with `None`. Rewards
+    /// accrued until now are kept at the previous rates.
+    pub fn set_staking_config(&mut self, config: Option<StakingConfig>) {
+        self.assert_role(Role::Treasurer);
+        if let Some(config) = &config {
+            require!(
+                config.rates.len() == RARITY_TIERS,
+                format!("Expected {} rates, one per rarity tier", RARITY_TIERS)
+            );
+        }
+        self.internal_checkpoint_reward_indexes();
+        self.staking_config = config;
+    }
+
+    pub fn get_staking_config(&self) -> Option<StakingConfig> {
+        self.staking_config.clone()
+    }
+
+    /// Add the attached deposit to the pool rewards are paid from in NEAR.
+    #[payable]
+    pub fn fund_reward_pool(&mut self) -> U128 {
+        self.reward_pool += env::attached_deposit();
+        self.reward_pool.into()
+    }
+
+    pub fn get_reward_pool(&self) -> U128 {
+        self.reward_pool.into()
+    }
+
+    /// Stake a warrior of the predecessor. Until it is unstaked, it earns rewards and cannot
+    /// be transferred or approved. Existing approvals are cleared.
+    #[payable]
+    pub fn stake(&mut self, token_id: TokenId) {
+        let initial_storage_usage = env::storage_usage();
+        self.expect_staking_config();
+        let owner_id = env::predecessor_account_id();
+        self.assert_not_blocked(&owner_id);
+        require!(
+            self.tokens.owner_by_id.get(&token_id).as_ref() == Some(&owner_id),
+            "Predecessor must own the token"
+        );
+        self.assert_not_staked(&token_id);
+        if let Some(by_id) = self.tokens.approvals_by_id.as_mut() {
+            by_id.remove(&token_id);
+        }
+        self.approval_expirations.remove(&token_id);
+
+        let rarity = self.rarity(&token_id);
+        self.stakes.insert(
+            &token_id,
+            &Stake {
+                owner_id: owner_id.clone(),
+                rarity: rarity as u8,
+                reward_index: self.current_reward_indexes()[rarity],
+            },
+        );
+        let mut staked = self.staked_by_owner.get(&owner_id).unwrap_or_default();
+        staked.push(token_id);
+        self.staked_by_owner.insert(&owner_id, &staked);
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Unstake a warrior, paying out its pending rewards. Rewards in NEAR the reward pool
+    /// cannot cover are kept owed and paid by a later claim. Returns the rewards paid.
+    #[payable]
+    pub fn unstake(&mut self, token_id: TokenId) -> Rewards {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        self.assert_not_blocked(&owner_id);
+        let stake = self
+            .stakes
+            .get(&token_id)
+            .unwrap_or_else(|| env::panic_str("Token is not staked"));
+        require!(stake.owner_id == owner_id, "Predecessor must own the token");
+        let reward = self.internal_claim_rewards(&owner_id, &[token_id.clone()]);
+
+        self.stakes.remove(&token_id);
+        let mut staked = self.staked_by_owner.get(&owner_id).unwrap_or_default();
+        staked.retain(|staked_token_id| staked_token_id != &token_id);
+        if staked.is_empty() {
+            self.staked_by_owner.remove(&owner_id);
+        } else {
+            self.staked_by_owner.insert(&owner_id, &staked);
+        }
+        reward
+    }
+
+    /// Pay out the pending rewards of every warrior staked by the predecessor, along with the
+    /// rewards it is owed.
+    #[payable]
+    pub fn claim_rewards(&mut self) -> Rewards {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        self.assert_not_blocked(&owner_id);
+        let staked = self.staked_by_owner.get(&owner_id).unwrap_or_default();
+        require!(
+            !staked.is_empty() || self.owed_rewards.get(&owner_id).is_some(),
+            "No staked tokens or owed rewards"
+        );
+        self.internal_claim_rewards(&owner_id, &staked)
+    }
+
+    pub fn nft_stake(&self, token_id: TokenId) -> Option<Stake> {
+        self.stakes.get(&token_id)
+    }
+
+    /// Returns the staked warriors of `account_id`.
+    pub fn get_staked_tokens(&self, account_id: AccountId) -> Vec<TokenId> {
+        self.staked_by_owner.get(&account_id).unwrap_or_default()
+    }
+
+    /// Returns the rewards `account_id` would be paid by claiming now.
+    pub fn get_pending_rewards(&self, account_id: AccountId) -> Rewards {
+        let indexes = self.current_reward_indexes();
+        let (near, points) = self
+            .staked_by_owner
+            .get(&account_id)
+            .unwrap_or_default()
+            .iter()
+            .map(|token_id| Self::pending_reward(&self.stakes.get(token_id).unwrap(), &indexes))
+            .fold(
+                (0u128, 0u128),
+                |(near, points), (stake_near, stake_points)| {
+                    (
+                        near.saturating_add(stake_near),
+                        points.saturating_add(stake_points),
+                    )
+                },
+            );
+        Rewards {
+            near: near.into(),
+            points: points.into(),
+        }
+    }
+
+    /// Returns the rewards in yoctoNEAR owed to `account_id` that the reward pool could not
+    /// cover yet.
+    pub fn get_owed_rewards(&self, account_id: AccountId) -> U128 {
+        self.owed_rewards.get(&account_id).unwrap_or(0).into()
+    }
+
+    /// Returns the reward points credited to `account_id`.
+    pub fn get_reward_points(&self, account_id: AccountId) -> U128 {
+        self.reward_points.get(&account_id).unwrap_or(0).into()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{RewardMode, Rewards, StakingConfig};
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const STAKE_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+    const SECOND: u64 = 1_000_000_000;
+
+    fn rewards(near: u128, points: u128) -> Rewards {
+        Rewards {
+            near: U128(near),
+            points: U128(points),
+        }
+    }
+
+    fn setup(reward_mode: RewardMode) -> (VMContextBuilder, Contract, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        contract.set_staking_config(Some(StakingConfig {
+            rates: vec![U128(10); 4],
+            reward_mode,
+        }));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let token_id = contract.nft_mint_default(accounts(1)).token_id;
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(STAKE_DEPOSIT)
+            .block_timestamp(0)
+            .build());
+        contract.stake(token_id.clone());
+        (context, contract, token_id)
+    }
+
+    #[test]
+    fn test_rewards_accrue_as_points() {
+        let (mut context, mut contract, token_id) = setup(RewardMode::Points);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .block_timestamp(5 * SECOND)
+            .build());
+        assert_eq!(contract.get_pending_rewards(accounts(1)), rewards(0, 50));
+        assert_eq!(contract.claim_rewards(), rewards(0, 50));
+        assert_eq!(contract.get_pending_rewards(accounts(1)), rewards(0, 0));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(7 * SECOND)
+            .build());
+        assert_eq!(contract.unstake(token_id.clone()), rewards(0, 20));
+        assert_eq!(contract.get_reward_points(accounts(1)), U128(70));
+        assert!(contract.nft_stake(token_id).is_none());
+    }
+
+    #[test]
+    fn test_rewards_paid_from_pool() {
+        let (mut context, mut contract, token_id) = setup(RewardMode::Near);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1_000)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.fund_reward_pool();
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(10 * SECOND)
+            .build());
+        assert_eq!(contract.unstake(token_id), rewards(100, 0));
+        assert_eq!(contract.get_reward_pool(), U128(900));
+    }
+
+    #[test]
+    fn test_unstake_with_short_pool() {
+        let (mut context, mut contract, token_id) = setup(RewardMode::Near);
+        testing_env!(context
+            .attached_deposit(30)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.fund_reward_pool();
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(10 * SECOND)
+            .build());
+        assert_eq!(contract.unstake(token_id.clone()), rewards(30, 0));
+        assert!(contract.nft_stake(token_id).is_none());
+        assert_eq!(contract.get_owed_rewards(accounts(1)), U128(70));
+
+        testing_env!(context
+            .attached_deposit(100)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.fund_reward_pool();
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        assert_eq!(contract.claim_rewards(), rewards(70, 0));
+        assert_eq!(contract.get_owed_rewards(accounts(1)), U128(0));
+    }
+
+    #[test]
+    fn test_config_changes_are_not_retroactive() {
+        let (mut context, mut contract, _) = setup(RewardMode::Points);
+        let config = |rate| {
+            Some(StakingConfig {
+                rates: vec![U128(rate); 4],
+                reward_mode: RewardMode::Points,
+            })
+        };
+        // 10 per second until 5s, nothing while disabled until 10s, then 20 per second
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(5 * SECOND)
+            .build());
+        contract.set_staking_config(None);
+        testing_env!(context.block_timestamp(10 * SECOND).build());
+        contract.set_staking_config(config(20));
+        testing_env!(context.block_timestamp(12 * SECOND).build());
+        assert_eq!(contract.get_pending_rewards(accounts(1)), rewards(0, 90));
+    }
+
+    #[test]
+    fn test_reward_index_saturates() {
+        let (mut context, mut contract, token_id) = setup(RewardMode::Points);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_staking_config(Some(StakingConfig {
+            rates: vec![U128(u128::MAX / 2); 4],
+            reward_mode: RewardMode::Points,
+        }));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(365 * 24 * 3600 * SECOND)
+            .build());
+        assert_eq!(contract.unstake(token_id.clone()), rewards(0, u128::MAX));
+        assert!(contract.nft_stake(token_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is staked")]
+    fn test_transfer_staked_token() {
+        let (mut context, mut contract, token_id) = setup(RewardMode::Points);
+        testing_env!(context.attached_deposit(1).build());
+        contract.nft_transfer(accounts(2), token_id, None, None);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::roles::Role;
+use crate::utils::random_u64;
+use crate::*;
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+
+const ATTACK_PER_LEVEL: u32 = 2;
+const DEFENSE_PER_LEVEL: u32 = 2;
+const HP_PER_LEVEL: u32 = 10;
+
+/// Game state of a warrior.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WarriorStats {
+    pub attack: u32,
+    pub defense: u32,
+    pub hp: u32,
+    pub xp: u64,
+    pub level: u32,
+}
+
+/// Largest `n` such that `n * n <= value`, found by binary search.
+fn integer_sqrt(value: u64) -> u64 {
+    // the square root of a u64 is below 2^32, whose square does not overflow
+    let (mut low, mut high) = (0u64, 1 << 32);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if mid * mid <= value {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Level reached with `xp` experience: level `n + 1` takes `100 * n^2` experience.
+pub(crate) fn level_for_xp(xp: u64) -> u32 {
+    integer_sqrt(xp / 100) as u32 + 1
+}
+
+impl WarriorStats {
+    /// Seed the stats of a newly minted `token_id` from the block's random seed.
+    pub(crate) fn random(token_id: &TokenId) -> Self {
+        let draw = |stat: &str, min: u32, spread: u32| {
+            min + (random_u64(format!("{}:{}", stat, token_id).as_bytes()) % u64::from(spread))
+                as u32
+        };
+        Self {
+            attack: draw("attack", 10, 20),
+            defense: draw("defense", 10, 20),
+            hp: draw("hp", 80, 40),
+            xp: 0,
+            level: 1,
+        }
+    }
+
+    /// Adds `amount` of experience, levelling up as many times as it allows. Experience and
+    /// stats saturate instead of overflowing.
+    pub(crate) fn gain_xp(&mut self, amount: u64) {
+        self.xp = self.xp.saturating_add(amount);
+        let level = level_for_xp(self.xp).max(self.level);
+        let gained = level - self.level;
+        self.level = level;
+        self.attack = self
+            .attack
+            .saturating_add(gained.saturating_mul(ATTACK_PER_LEVEL));
+        self.defense = self
+            .defense
+            .saturating_add(gained.saturating_mul(DEFENSE_PER_LEVEL));
+        self.hp = self.hp.saturating_add(gained.saturating_mul(HP_PER_LEVEL));
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_gain_xp(&mut self, token_id: &TokenId, amount: u64) -> WarriorStats {
+        let mut stats = self
+            .warrior_stats
+            .get(token_id)
+            .unwrap_or_else(|| env::panic_str("Token has no stats"));
+        stats.gain_xp(amount);
+        self.warrior_stats.insert(token_id, &stats);
+        stats
+    }
+
+    /// Surfaces the stats of a warrior in the `extra` field of its metadata.
+    pub(crate) fn internal_attach_stats(
+        &self,
+        token_id: &TokenId,
+        metadata: TokenMetadata,
+    ) -> TokenMetadata {
+        match self.warrior_stats.get(token_id) {
+            Some(stats) => TokenMetadata {
+                extra: Some(serde_json::to_string(&stats).unwrap()),
+                ..metadata
+            },
+            None => metadata,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the game server account allowed to award experience to warriors.
+    pub fn set_game_server(&mut self, account_id: Option<AccountId>) {
+        self.assert_role(Role::Admin);
+        self.game_server_id = account_id;
+    }
+
+    pub fn get_game_server(&self) -> Option<AccountId> {
+        self.game_server_id.clone()
+    }
+
+    /// Award `amount` experience to a warrior, levelling it up once it has enough.
+    pub fn gain_xp(&mut self, token_id: TokenId, amount: U64) -> WarriorStats {
+        require!(
+            Some(env::predecessor_account_id()) == self.game_server_id,
+            "Only the game server can award experience"
+        );
+        self.internal_gain_xp(&token_id, amount.0)
+    }
+
+    pub fn nft_stats(&self, token_id: TokenId) -> Option<WarriorStats> {
+        self.warrior_stats.get(&token_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::WarriorStats;
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::U64;
+    use near_sdk::serde_json;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> (VMContextBuilder, Contract, TokenId) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        contract.set_game_server(Some(accounts(2)));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let token_id = contract.nft_mint_default(accounts(1)).token_id;
+        (context, contract, token_id)
+    }
+
+    #[test]
+    fn test_level_up() {
+        let mut stats = WarriorStats {
+            attack: 10,
+            defense: 10,
+            hp: 100,
+            xp: 0,
+            level: 1,
+        };
+        stats.gain_xp(99);
+        assert_eq!(stats.level, 1);
+        // 100 xp reaches level 2, 400 xp reaches level 3
+        stats.gain_xp(301);
+        assert_eq!(stats.level, 3);
+        assert_eq!(stats.attack, 14);
+        assert_eq!(stats.hp, 120);
+    }
+
+    #[test]
+    fn test_gain_xp_saturates() {
+        let mut stats = WarriorStats {
+            attack: 10,
+            defense: 10,
+            hp: 100,
+            xp: 0,
+            level: 1,
+        };
+        stats.gain_xp(u64::MAX);
+        stats.gain_xp(u64::MAX);
+        assert_eq!(stats.xp, u64::MAX);
+        assert_eq!(stats.level, 429_496_730);
+        assert_eq!(stats.hp, u32::MAX);
+        assert_eq!(super::level_for_xp(399), 2);
+        assert_eq!(super::level_for_xp(400), 3);
+        assert_eq!(super::integer_sqrt(u64::MAX), u64::from(u32::MAX));
+    }
+
+    #[test]
+    fn test_stats_in_extra() {
+        let (mut context, mut contract, token_id) = setup();
+        let stats = contract.nft_stats(token_id.clone()).unwrap();
+        assert_eq!(stats.level, 1);
+        assert!(stats.attack >= 10 && stats.attack < 30);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let stats = contract.gain_xp(token_id.clone(), U64(100));
+        assert_eq!(stats.level, 2);
+
+        let extra = contract
+            .nft_token(token_id)
+            .unwrap()
+            .metadata
+            .unwrap()
+            .extra
+            .unwrap();
+        assert_eq!(extra, serde_json::to_string(&stats).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the game server can award experience")]
+    fn test_gain_xp_not_game_server() {
+        let (_, mut contract, token_id) = setup();
+        contract.gain_xp(token_id, U64(100));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::utils::random_u64;
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::Serialize;
+
+const BACKGROUNDS: [&str; 6] = [
+    "#1d3557", "#2a9d8f", "#e9c46a", "#6d597a", "#264653", "#8d0801",
+];
+const SKINS: [&str; 4] = ["#ffdbac", "#f1c27d", "#c68642", "#8d5524"];
+const ARMORS: [&str; 5] = ["#adb5bd", "#b08d57", "#4a4e69", "#d4af37", "#2b2d42"];
+// weapon layers, drawn in the color of the armor
+const WEAPONS: [&str; 4] = [
+    "<path d='M220 90v150M205 220h30' stroke-width='10'/>",
+    "<path d='M220 90v160' stroke-width='8'/><path d='M220 100q40 20 0 60z' stroke-width='4'/>",
+    "<path d='M220 60v200' stroke-width='6'/><path d='M210 80l10-30 10 30z' stroke-width='4'/>",
+    "<path d='M220 110v150' stroke-width='8'/><rect x='195' y='90' width='50' height='30'/>",
+];
+const HELMETS: [&str; 3] = [
+    "",
+    "<path d='M115 95a35 35 0 0 1 70 0z'/>",
+    "<path d='M115 95a35 35 0 0 1 70 0z'/><path d='M150 60v-25' stroke-width='6'/>",
+];
+
+/// The layers of a warrior's on-chain art, as indexes into the palettes above.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WarriorTraits {
+    pub background: u8,
+    pub skin: u8,
+    pub armor: u8,
+    pub weapon: u8,
+    pub helmet: u8,
+}
+
+impl WarriorTraits {
+    /// Draw random traits for `token_id`.
+    pub(crate) fn random(token_id: &TokenId) -> Self {
+        let draw = |layer: &str, options: usize| {
+            (random_u64(format!("{}:{}", layer, token_id).as_bytes()) % options as u64) as u8
+        };
+        Self {
+            background: draw("background", BACKGROUNDS.len()),
+            skin: draw("skin", SKINS.len()),
+            armor: draw("armor", ARMORS.len()),
+            weapon: draw("weapon", WEAPONS.len()),
+            helmet: draw("helmet", HELMETS.len()),
+        }
+    }
+
+    /// Traits of a child of `a` and `b`: each layer comes from either parent at random, or
+    /// one time in ten mutates into a random one.
+    pub(crate) fn inherit(a: &Self, b: &Self, token_id: &TokenId) -> Self {
+        let pick = |layer: &str, from_a: u8, from_b: u8, options: usize| {
+            let roll = random_u64(format!("{}:{}", layer, token_id).as_bytes());
+            match roll % 10 {
+                0 => ((roll / 10) % options as u64) as u8,
+                _ if (roll / 10) % 2 == 0 => from_a,
+                _ => from_b,
+            }
+        };
+        Self {
+            background: pick("background", a.background, b.background, BACKGROUNDS.len()),
+            skin: pick("skin", a.skin, b.skin, SKINS.len()),
+            armor: pick("armor", a.armor, b.armor, ARMORS.len()),
+            weapon: pick("weapon", a.weapon, b.weapon, WEAPONS.len()),
+            helmet: pick("helmet", a.helmet, b.helmet, HELMETS.len()),
+        }
+    }
+
+    /// Rarity tier from 0 (common) to 3, counting the layers drawn from the last, rarest
+    /// option of their palette.
+    pub(crate) fn rarity(&self) -> usize {
+        let rarest = [
+            usize::from(self.background) == BACKGROUNDS.len() - 1,
+            usize::from(self.skin) == SKINS.len() - 1,
+            usize::from(self.armor) == ARMORS.len() - 1,
+            usize::from(self.weapon) == WEAPONS.len() - 1,
+            usize::from(self.helmet) == HELMETS.len() - 1,
+        ];
+        rarest.iter().filter(|&&rarest| rarest).count().min(3)
+    }
+
+    pub(crate) fn render_svg(&self) -> String {
+        let armor = ARMORS[self.armor as usize];
+        format!(
+            "<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 300 300'>\
+             <rect width='300' height='300' fill='{background}'/>\
+             <circle cx='150' cy='95' r='35' fill='{skin}'/>\
+             <rect x='105' y='135' width='90' height='110' rx='15' fill='{armor}'/>\
+             <rect x='115' y='245' width='25' height='40' fill='{skin}'/>\
+             <rect x='160' y='245' width='25' height='40' fill='{skin}'/>\
+             <g fill='{armor}' stroke='{armor}'>{helmet}</g>\
+             <g fill='{armor}' stroke='{armor}'>{weapon}</g>\
+             </svg>",
+            background = BACKGROUNDS[self.background as usize],
+            skin = SKINS[self.skin as usize],
+            armor = armor,
+            helmet = HELMETS[self.helmet as usize],
+            weapon = WEAPONS[self.weapon as usize],
+        )
+    }
+}
+
+impl Contract {
+    /// Renders the art of a token with on-chain traits into its `media`, so that it never
+    /// depends on an off-chain host.
+    pub(crate) fn internal_render_media(
+        &self,
+        token_id: &TokenId,
+        metadata: TokenMetadata,
+    ) -> TokenMetadata {
+        match self.token_traits.get(token_id) {
+            Some(traits) => {
+                let svg = traits.render_svg();
+                TokenMetadata {
+                    media: Some(format!(
+                        "data:image/svg+xml;base64,{}",
+                        base64::encode(&svg)
+                    )),
+                    media_hash: Some(Base64VecU8(env::sha256(svg.as_bytes()))),
+                    ..metadata
+                }
+            }
+            None => metadata,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn nft_traits(&self, token_id: TokenId) -> Option<WarriorTraits> {
+        self.token_traits.get(&token_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_media_rendered_from_traits() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.init_mint_pool(10);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let token_id = contract.nft_mint_default(accounts(1)).token_id;
+
+        let traits = contract.nft_traits(token_id.clone()).unwrap();
+        let metadata = contract.nft_token(token_id).unwrap().metadata.unwrap();
+        let media = metadata.media.unwrap();
+        let encoded = media.strip_prefix("data:image/svg+xml;base64,").unwrap();
+        let svg = String::from_utf8(base64::decode(encoded).unwrap()).unwrap();
+        assert_eq!(svg, traits.render_svg());
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(metadata.media_hash.unwrap().0, env::sha256(svg.as_bytes()));
+    }
+}
@@ -0,0 +1,33 @@
+//! Helpers shared by the unit tests of the modules.
+use crate::*;
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+
+pub(crate) const MINT_STORAGE_COST: u128 = 11000000000000000000000;
+// covers the storage of a token minted with the default metadata and its extra state
+pub(crate) const MINT_DEPOSIT: u128 = 20_000_000_000_000_000_000_000;
+
+pub(crate) fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .current_account_id(accounts(0))
+        .signer_account_id(predecessor_account_id.clone())
+        .predecessor_account_id(predecessor_account_id);
+    builder
+}
+
+pub(crate) fn sample_token_metadata() -> TokenMetadata {
+    TokenMetadata {
+        title: Some("Olympus Mons".into()),
+        description: Some("The tallest mountain in the charted solar system".into()),
+        media: None,
+        media_hash: None,
+        copies: Some(1u64),
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+    }
+}
@@ -0,0 +1,273 @@
+//! Transfer policy set by admins, applied by `nft_transfer` and `nft_transfer_call` on
+//! top of the checks every transfer goes through: receivers can be restricted to an allowlist
+//! or kept off a denylist, new tokens must be held for a minimum period before they move, and
+//! single tokens can be locked in place. Loans and the vault apply it as well, both when they
+//! take a token into custody and when they release it to anyone but its previous owner.
+use crate::roles::Role;
+use crate::*;
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// How the listed receivers are treated.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ReceiverList {
+    /// The list is ignored.
+    Off,
+    /// Only listed accounts can receive tokens.
+    Allow,
+    /// Listed accounts cannot receive tokens.
+    Deny,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferPolicy {
+    pub receiver_list: ReceiverList,
+    /// Time a token must be held after its mint before it can be transferred, in nanoseconds.
+    pub min_holding_period: U64,
+}
+
+impl Default for TransferPolicy {
+    fn default() -> Self {
+        Self {
+            receiver_list: ReceiverList::Off,
+            min_holding_period: U64(0),
+        }
+    }
+}
+
+impl Contract {
+    /// Panics if the transfer policy forbids transferring `token_id` to `receiver_id` now. The
+    /// contract itself, taking a token into custody, is not subject to the receiver list.
+    pub(crate) fn assert_transfer_allowed(&self, token_id: &TokenId, receiver_id: &AccountId) {
+        let listed = self.listed_receivers.contains(receiver_id);
+        let receiver_list = if receiver_id == &env::current_account_id() {
+            ReceiverList::Off
+        } else {
+            self.transfer_policy.receiver_list
+        };
+        match receiver_list {
+            ReceiverList::Off => {}
+            ReceiverList::Allow => require!(listed, "Receiver is not on the allowlist"),
+            ReceiverList::Deny => require!(!listed, "Receiver is on the denylist"),
+        }
+        let now = env::block_timestamp();
+        if let Some(minted_at) = self.minted_at.get(token_id) {
+            require!(
+                now >= minted_at.saturating_add(self.transfer_policy.min_holding_period.0),
+                "Token is still in its holding period"
+            );
+        }
+        if let Some(locked_until) = self.transfer_locks.get(token_id) {
+            require!(now >= locked_until, "Token transfers are locked");
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn set_transfer_policy(&mut self, policy: TransferPolicy) {
+        self.assert_role(Role::Admin);
+        self.transfer_policy = policy;
+    }
+
+    pub fn get_transfer_policy(&self) -> TransferPolicy {
+        self.transfer_policy.clone()
+    }
+
+    /// Add accounts to the list of the receiver policy.
+    #[payable]
+    pub fn add_listed_receivers(&mut self, account_ids: Vec<AccountId>) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Admin);
+        for account_id in &account_ids {
+            self.listed_receivers.insert(account_id);
+        }
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    pub fn remove_listed_receivers(&mut self, account_ids: Vec<AccountId>) {
+        self.assert_role(Role::Admin);
+        for account_id in &account_ids {
+            self.listed_receivers.remove(account_id);
+        }
+    }
+
+    pub fn is_listed_receiver(&self, account_id: AccountId) -> bool {
+        self.listed_receivers.contains(&account_id)
+    }
+
+    /// Lock the transfers of `token_id` until the block timestamp `until`, or until unlocked
+    /// if `None`. Requires the `Minter` role.
+    #[payable]
+    pub fn lock_transfers(&mut self, token_id: TokenId, until: Option<U64>) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_role(Role::Minter);
+        require!(
+            self.tokens.owner_by_id.get(&token_id).is_some(),
+            "Token not found"
+        );
+        self.transfer_locks
+            .insert(&token_id, &until.map_or(u64::MAX, |until| until.0));
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    pub fn unlock_transfers(&mut self, token_id: TokenId) {
+        self.assert_role(Role::Minter);
+        self.transfer_locks.remove(&token_id);
+    }
+
+    /// Returns the block timestamp the transfers of `token_id` are locked until, `u64::MAX`
+    /// standing for a lock without end.
+    pub fn get_transfer_lock(&self, token_id: TokenId) -> Option<U64> {
+        self.transfer_locks.get(&token_id).map(U64)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::{ReceiverList, TransferPolicy};
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup(policy: TransferPolicy) -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        contract.set_transfer_policy(policy);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .block_timestamp(100)
+            .build());
+        contract.nft_mint("0".to_string(), accounts(1), sample_token_metadata());
+        testing_env!(context.storage_usage(env::storage_usage()).build());
+        contract.add_listed_receivers(vec![accounts(2)]);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(200)
+            .build());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let (_, mut contract) = setup(TransferPolicy {
+            receiver_list: ReceiverList::Allow,
+            min_holding_period: U64(0),
+        });
+        assert_eq!(
+            contract.get_transfer_policy().receiver_list,
+            ReceiverList::Allow
+        );
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is still in its holding period")]
+    fn test_holding_period() {
+        let (_, mut contract) = setup(TransferPolicy {
+            receiver_list: ReceiverList::Deny,
+            min_holding_period: U64(101),
+        });
+        contract.nft_transfer(accounts(3), "0".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token transfers are locked")]
+    fn test_transfer_lock() {
+        let (mut context, mut contract) = setup(TransferPolicy::default());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.lock_transfers("0".to_string(), Some(U64(300)));
+        assert_eq!(contract.get_transfer_lock("0".to_string()), Some(U64(300)));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(3), "0".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token transfers are locked")]
+    fn test_locked_token_cannot_be_escrowed() {
+        let (mut context, mut contract) = setup(TransferPolicy::default());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.lock_transfers("0".to_string(), None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.request_loan("0".to_string(), U128(1_000), U128(0), U64(50));
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver is not on the allowlist")]
+    fn test_unlisted_lender_cannot_fund() {
+        let (mut context, mut contract) = setup(TransferPolicy {
+            receiver_list: ReceiverList::Allow,
+            min_holding_period: U64(0),
+        });
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let loan_id = contract.request_loan("0".to_string(), U128(1_000), U128(0), U64(50));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1_000 + MINT_DEPOSIT)
+            .predecessor_account_id(accounts(3))
+            .build());
+        contract.fund_loan(loan_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token transfers are locked")]
+    fn test_lock_survives_escrow() {
+        let (mut context, mut contract) = setup(TransferPolicy::default());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let loan_id = contract.request_loan("0".to_string(), U128(1_000), U128(0), U64(50));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1_000 + MINT_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.fund_loan(loan_id);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.lock_transfers("0".to_string(), None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(250)
+            .build());
+        contract.claim_defaulted_loan(loan_id);
+    }
+}
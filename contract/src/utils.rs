@@ -0,0 +1,46 @@
+use near_sdk::{env, require, Balance, Promise};
+
+#[allow(clippy::assign_op_pattern, clippy::ptr_offset_with_cast)]
+mod u256 {
+    uint::construct_uint! {
+        /// 256-bit unsigned integer for intermediate results that overflow `u128`.
+        pub(crate) struct U256(4);
+    }
+}
+use u256::U256;
+
+/// Computes `a * b / c` without overflowing on the product.
+pub(crate) fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    (U256::from(a) * U256::from(b) / U256::from(c)).as_u128()
+}
+
+/// Pseudo-random number derived from the block's random seed and `salt`.
+///
+/// The seed is the same for every call within a receipt, so callers drawing several numbers
+/// in one call must vary the salt.
+pub(crate) fn random_u64(salt: &[u8]) -> u64 {
+    let mut input = env::random_seed();
+    input.extend_from_slice(salt);
+    let hash = env::sha256(&input);
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Requires the attached deposit to cover `price` plus the cost of `storage_used` bytes, and
+/// refunds the rest to the predecessor.
+pub(crate) fn refund_deposit_with_price(storage_used: u64, price: Balance) {
+    let required_cost = env::storage_byte_cost() * Balance::from(storage_used) + price;
+    let attached_deposit = env::attached_deposit();
+    require!(
+        required_cost <= attached_deposit,
+        format!(
+            "Must attach {} yoctoNEAR to cover the price and storage",
+            required_cost
+        )
+    );
+    let refund = attached_deposit - required_cost;
+    if refund > 1 {
+        Promise::new(env::predecessor_account_id()).transfer(refund);
+    }
+}
@@ -0,0 +1,289 @@
+//! Validity windows of tokens, from the `starts_at` and `expires_at` of their metadata. A token
+//! can only be transferred, equipped, unequipped or redeemed within its window, and loans and
+//! the vault only take it into custody or release it to anyone but its previous owner within
+//! it. Admins can burn expired tokens to reclaim their storage.
+//!
+//! Both fields hold either a Unix epoch in milliseconds, as NEP-177 specifies, or an ISO 8601
+//! date time such as `2024-05-01T12:00:00Z`.
+use crate::roles::Role;
+use crate::*;
+use std::convert::TryFrom;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const NANOS_PER_MILLISECOND: u64 = 1_000_000;
+
+/// Days from 1970-01-01 to the given date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * i64::from((month + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Splits a `Z` or `±HH:MM` suffix off an ISO 8601 time, returning the offset in seconds.
+fn split_offset(time: &str) -> Option<(&str, i64)> {
+    if let Some(time) = time.strip_suffix('Z') {
+        return Some((time, 0));
+    }
+    let sign_index = time.len().checked_sub(6)?;
+    let sign = match time.as_bytes().get(sign_index) {
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return Some((time, 0)),
+    };
+    let (hours, minutes) = time[sign_index + 1..].split_once(':')?;
+    let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+    Some((&time[..sign_index], sign * offset))
+}
+
+fn parse_iso8601(value: &str) -> Option<u64> {
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let mut date = date.splitn(3, '-');
+    let year: i64 = date.next()?.parse().ok()?;
+    let month: u32 = date.next()?.parse().ok()?;
+    let day: u32 = date.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut seconds = days_from_civil(year, month, day) * 86_400;
+    let mut nanos = 0;
+
+    if let Some(time) = time {
+        let (time, offset) = split_offset(time)?;
+        let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+        let mut time = time.splitn(3, ':');
+        let hours: i64 = time.next()?.parse().ok()?;
+        let minutes: i64 = time.next()?.parse().ok()?;
+        let secs: i64 = time.next().map_or(Some(0), |secs| secs.parse().ok())?;
+        if hours > 23 || minutes > 59 || secs > 60 {
+            return None;
+        }
+        seconds += hours * 3600 + minutes * 60 + secs - offset;
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        nanos = fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(9)
+            .fold(0, |nanos, digit| nanos * 10 + u64::from(digit - b'0'));
+    }
+    u64::try_from(seconds)
+        .ok()?
+        .checked_mul(NANOS_PER_SECOND)?
+        .checked_add(nanos)
+}
+
+/// Parses a `starts_at` or `expires_at` value into a block timestamp in nanoseconds.
+pub(crate) fn parse_timestamp(value: &str) -> Option<u64> {
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        return value
+            .parse::<u64>()
+            .ok()?
+            .checked_mul(NANOS_PER_MILLISECOND);
+    }
+    parse_iso8601(value)
+}
+
+/// Validity window of metadata as block timestamps, `None` standing for no bound. Values that
+/// do not parse, as tokens minted before windows were enforced may hold, are no bound either.
+fn window(metadata: &TokenMetadata) -> (Option<u64>, Option<u64>) {
+    let bound = |value: &Option<String>| value.as_deref().and_then(parse_timestamp);
+    (bound(&metadata.starts_at), bound(&metadata.expires_at))
+}
+
+/// Panics unless `starts_at` and `expires_at` of `metadata` parse and are in order.
+pub(crate) fn assert_valid_window(metadata: &TokenMetadata) {
+    for value in [&metadata.starts_at, &metadata.expires_at]
+        .iter()
+        .copied()
+        .flatten()
+    {
+        require!(
+            parse_timestamp(value).is_some(),
+            format!("Invalid timestamp {}", value)
+        );
+    }
+    if let (Some(starts_at), Some(expires_at)) = window(metadata) {
+        require!(starts_at < expires_at, "Token must start before it expires");
+    }
+}
+
+impl Contract {
+    fn token_window(&self, token_id: &TokenId) -> (Option<u64>, Option<u64>) {
+        self.tokens
+            .token_metadata_by_id
+            .as_ref()
+            .and_then(|by_id| by_id.get(token_id))
+            .map_or((None, None), |metadata| window(&metadata))
+    }
+
+    fn is_expired(&self, token_id: &TokenId) -> bool {
+        let (_, expires_at) = self.token_window(token_id);
+        expires_at.map_or(false, |expires_at| env::block_timestamp() >= expires_at)
+    }
+
+    pub(crate) fn assert_active(&self, token_id: &TokenId) {
+        let (starts_at, expires_at) = self.token_window(token_id);
+        let now = env::block_timestamp();
+        require!(
+            starts_at.map_or(true, |starts_at| now >= starts_at),
+            "Token is not valid yet"
+        );
+        require!(
+            expires_at.map_or(true, |expires_at| now < expires_at),
+            "Token has expired"
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Whether `token_id` exists and the current block is within its validity window.
+    pub fn nft_is_active(&self, token_id: TokenId) -> bool {
+        if self.tokens.owner_by_id.get(&token_id).is_none() {
+            return false;
+        }
+        let (starts_at, expires_at) = self.token_window(&token_id);
+        let now = env::block_timestamp();
+        starts_at.map_or(true, |starts_at| now >= starts_at)
+            && expires_at.map_or(true, |expires_at| now < expires_at)
+    }
+
+    /// Burn those of `token_ids` which have expired, skipping staked tokens and tokens held in
+    /// the custody of the contract. Returns the burned tokens. Requires the `Admin` role.
+    pub fn burn_expired_tokens(&mut self, token_ids: Vec<TokenId>) -> Vec<TokenId> {
+        self.assert_role(Role::Admin);
+        let custodian_id = env::current_account_id();
+        let mut burned = vec![];
+        for token_id in token_ids {
+            let owner_id = match self.tokens.owner_by_id.get(&token_id) {
+                Some(owner_id) => owner_id,
+                None => continue,
+            };
+            if owner_id == custodian_id
+                || self.stakes.get(&token_id).is_some()
+                || !self.is_expired(&token_id)
+            {
+                continue;
+            }
+            self.internal_burn(&token_id, Some("expired"));
+            burned.push(token_id);
+        }
+        burned
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::parse_timestamp;
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_DEPOSIT};
+    use crate::*;
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    // 2024-05-01T12:00:00Z
+    const MAY_FIRST: u64 = 1_714_564_800_000_000_000;
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_DEPOSIT)
+            .build());
+        let mut ticket = sample_token_metadata();
+        ticket.starts_at = Some("2024-05-01T12:00:00Z".to_string());
+        ticket.expires_at = Some("2024-05-02".to_string());
+        contract.nft_mint("ticket".to_string(), accounts(1), ticket);
+        testing_env!(context.storage_usage(env::storage_usage()).build());
+        contract.nft_mint("0".to_string(), accounts(1), sample_token_metadata());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1714564800000"), Some(MAY_FIRST));
+        assert_eq!(parse_timestamp("2024-05-01T12:00:00Z"), Some(MAY_FIRST));
+        assert_eq!(
+            parse_timestamp("2024-05-01T14:00:00.5+02:00"),
+            Some(MAY_FIRST + 500_000_000)
+        );
+        assert_eq!(parse_timestamp("1970-01-01"), Some(0));
+        assert_eq!(parse_timestamp("2024-13-01"), None);
+        assert_eq!(parse_timestamp("tomorrow"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid timestamp tomorrow")]
+    fn test_mint_invalid_timestamp() {
+        let (_, mut contract) = setup();
+        let mut ticket = sample_token_metadata();
+        ticket.expires_at = Some("tomorrow".to_string());
+        contract.nft_mint("1".to_string(), accounts(1), ticket);
+    }
+
+    #[test]
+    fn test_legacy_timestamp_is_unbounded() {
+        let (mut context, mut contract) = setup();
+        // stored before windows were checked at mint
+        let mut legacy = sample_token_metadata();
+        legacy.starts_at = Some("someday".to_string());
+        contract
+            .tokens
+            .token_metadata_by_id
+            .as_mut()
+            .unwrap()
+            .insert(&"0".to_string(), &legacy);
+        assert!(contract.nft_is_active("0".to_string()));
+        testing_env!(context
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is not valid yet")]
+    fn test_transfer_before_start() {
+        let (mut context, mut contract) = setup();
+        assert!(!contract.nft_is_active("ticket".to_string()));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(MAY_FIRST - 1)
+            .build());
+        contract.nft_transfer(accounts(2), "ticket".to_string(), None, None);
+    }
+
+    #[test]
+    fn test_burn_expired_tokens() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.block_timestamp(MAY_FIRST).build());
+        assert!(contract.nft_is_active("ticket".to_string()));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .block_timestamp(MAY_FIRST + 12 * 3600 * 1_000_000_000)
+            .build());
+        assert!(!contract.nft_is_active("ticket".to_string()));
+        assert_eq!(
+            contract.burn_expired_tokens(vec!["ticket".to_string(), "0".to_string()]),
+            vec!["ticket".to_string()]
+        );
+        assert!(contract.nft_token("ticket".to_string()).is_none());
+        assert!(contract.nft_is_active("0".to_string()));
+    }
+}
@@ -0,0 +1,461 @@
+//! Fractional ownership of a single token. The token is locked in the custody of the contract,
+//! which issues NEP-141 shares of it. Anyone can buy the token out at the reserve price, after
+//! which the share holders redeem the proceeds pro rata by burning their shares. Once every
+//! share is redeemed, another token can be fractionalized.
+use crate::*;
+use near_contract_standards::fungible_token::core::FungibleTokenCore;
+use near_contract_standards::fungible_token::events::{FtBurn, FtMint};
+use near_contract_standards::fungible_token::metadata::{
+    FungibleTokenMetadata, FungibleTokenMetadataProvider,
+};
+use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
+use near_contract_standards::impl_fungible_token_storage;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Balance;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Vault {
+    pub token_id: TokenId,
+    pub curator_id: AccountId,
+    pub reserve_price: U128,
+    pub share_metadata: FungibleTokenMetadata,
+    pub buyer_id: Option<AccountId>,
+    /// Proceeds of the buyout which have not been redeemed yet.
+    pub proceeds: U128,
+}
+
+impl_fungible_token_storage!(Contract, shares);
+
+/// Shares move like any NEP-141 token, except to or from blocked accounts.
+#[near_bindgen]
+impl FungibleTokenCore for Contract {
+    #[payable]
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        self.assert_not_blocked(&env::predecessor_account_id());
+        self.assert_not_blocked(&receiver_id);
+        self.shares.ft_transfer(receiver_id, amount, memo)
+    }
+
+    #[payable]
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.assert_not_blocked(&env::predecessor_account_id());
+        self.assert_not_blocked(&receiver_id);
+        self.shares.ft_transfer_call(receiver_id, amount, memo, msg)
+    }
+
+    fn ft_total_supply(&self) -> U128 {
+        self.shares.ft_total_supply()
+    }
+
+    fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.shares.ft_balance_of(account_id)
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenResolver for Contract {
+    #[private]
+    fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        let (used_amount, _) =
+            self.shares
+                .internal_ft_resolve_transfer(&sender_id, receiver_id, amount);
+        used_amount.into()
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenMetadataProvider for Contract {
+    fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.vault
+            .as_ref()
+            .map(|vault| vault.share_metadata.clone())
+            .unwrap_or_else(|| env::panic_str("No token has been fractionalized"))
+    }
+}
+
+impl Contract {
+    fn expect_vault(&self) -> &Vault {
+        self.vault
+            .as_ref()
+            .unwrap_or_else(|| env::panic_str("No token has been fractionalized"))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Lock a token of the predecessor in the vault and issue `total_shares` shares of it to
+    /// the predecessor. The attached deposit must cover the storage of the vault.
+    #[payable]
+    pub fn fractionalize(
+        &mut self,
+        token_id: TokenId,
+        total_shares: U128,
+        reserve_price: U128,
+        share_metadata: FungibleTokenMetadata,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        require!(
+            self.vault
+                .as_ref()
+                .map_or(true, |vault| vault.buyer_id.is_some()
+                    && self.shares.total_supply == 0),
+            "A token is fractionalized until it is bought out and all its shares are redeemed"
+        );
+        share_metadata.assert_valid();
+        require!(total_shares.0 > 0, "Total shares must be positive");
+        let curator_id = env::predecessor_account_id();
+        self.assert_not_blocked(&curator_id);
+        require!(
+            self.tokens.owner_by_id.get(&token_id).as_ref() == Some(&curator_id),
+            "Predecessor must own the token"
+        );
+        self.assert_not_staked(&token_id);
+        self.assert_transfer_allowed(&token_id, &env::current_account_id());
+        self.assert_active(&token_id);
+        self.assert_not_checked_in(&token_id);
+
+        if let Some(by_id) = self.tokens.approvals_by_id.as_mut() {
+            by_id.remove(&token_id);
+        }
+        self.approval_expirations.remove(&token_id);
+        self.internal_clear_rental(&token_id);
+        self.internal_move_token(
+            &token_id,
+            &curator_id,
+            &env::current_account_id(),
+            "fractionalize",
+        );
+
+        if !self.shares.accounts.contains_key(&curator_id) {
+            self.shares.internal_register_account(&curator_id);
+        }
+        self.shares.internal_deposit(&curator_id, total_shares.0);
+        FtMint {
+            owner_id: &curator_id,
+            amount: &total_shares,
+            memo: Some("fractionalize"),
+        }
+        .emit();
+
+        self.vault = Some(Vault {
+            token_id,
+            curator_id,
+            reserve_price,
+            share_metadata,
+            buyer_id: None,
+            proceeds: U128(0),
+        });
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Buy the token out of the vault by paying the reserve price, which the share holders
+    /// can then redeem.
+    #[payable]
+    pub fn buyout(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let vault = self.expect_vault();
+        require!(
+            vault.buyer_id.is_none(),
+            "The token has already been bought out"
+        );
+        let token_id = vault.token_id.clone();
+        let reserve_price: Balance = vault.reserve_price.into();
+        let buyer_id = env::predecessor_account_id();
+        self.assert_not_blocked(&buyer_id);
+        self.assert_transfer_allowed(&token_id, &buyer_id);
+        self.assert_active(&token_id);
+        self.assert_not_checked_in(&token_id);
+
+        self.internal_move_token(&token_id, &env::current_account_id(), &buyer_id, "buyout");
+        let vault = self.vault.as_mut().unwrap();
+        vault.buyer_id = Some(buyer_id);
+        vault.proceeds = reserve_price.into();
+        utils::refund_deposit_with_price(
+            env::storage_usage().saturating_sub(initial_storage_usage),
+            reserve_price,
+        );
+    }
+
+    /// Burn all the shares of the predecessor for their part of the buyout proceeds.
+    pub fn redeem_shares(&mut self) -> U128 {
+        let vault = self.expect_vault();
+        require!(
+            vault.buyer_id.is_some(),
+            "The token has not been bought out yet"
+        );
+        let proceeds: Balance = vault.proceeds.into();
+        let holder_id = env::predecessor_account_id();
+        self.assert_not_blocked(&holder_id);
+        let shares = self.shares.ft_balance_of(holder_id.clone()).0;
+        require!(shares > 0, "No shares to redeem");
+
+        let amount = utils::mul_div(proceeds, shares, self.shares.total_supply);
+        self.shares.internal_withdraw(&holder_id, shares);
+        FtBurn {
+            owner_id: &holder_id,
+            amount: &U128(shares),
+            memo: Some("redeem"),
+        }
+        .emit();
+        self.vault.as_mut().unwrap().proceeds = (proceeds - amount).into();
+        if amount > 0 {
+            Promise::new(holder_id).transfer(amount);
+        }
+        amount.into()
+    }
+
+    pub fn get_vault(&self) -> Option<&Vault> {
+        self.vault.as_ref()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::test_utils::{get_context, sample_token_metadata, MINT_STORAGE_COST};
+    use crate::transfer_policy::{ReceiverList, TransferPolicy};
+    use crate::*;
+    use near_contract_standards::fungible_token::core::FungibleTokenCore;
+    use near_contract_standards::fungible_token::metadata::{
+        FungibleTokenMetadata, FT_METADATA_SPEC,
+    };
+    use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const VAULT_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+    const RESERVE_PRICE: u128 = 1_000;
+
+    fn share_metadata() -> FungibleTokenMetadata {
+        FungibleTokenMetadata {
+            spec: FT_METADATA_SPEC.to_string(),
+            name: "Olympus Mons shares".to_string(),
+            symbol: "MONS".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 0,
+        }
+    }
+
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint("0".to_string(), accounts(1), sample_token_metadata());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.fractionalize(
+            "0".to_string(),
+            U128(100),
+            U128(RESERVE_PRICE),
+            share_metadata(),
+        );
+        (context, contract)
+    }
+
+    #[test]
+    fn test_buyout_and_redeem() {
+        let (mut context, mut contract) = setup();
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(0)
+        );
+        assert_eq!(contract.ft_balance_of(accounts(1)), U128(100));
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.ft_transfer(accounts(2), U128(25), None);
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RESERVE_PRICE + VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(3))
+            .build());
+        contract.buyout();
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            accounts(3)
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(0)
+            .predecessor_account_id(accounts(2))
+            .build());
+        assert_eq!(contract.redeem_shares(), U128(250));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.redeem_shares(), U128(750));
+        assert_eq!(contract.ft_total_supply(), U128(0));
+        assert_eq!(contract.get_vault().unwrap().proceeds, U128(0));
+    }
+
+    #[test]
+    fn test_redeem_large_supply_and_next_vault() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        for token_id in ["0", "1"] {
+            testing_env!(context
+                .storage_usage(env::storage_usage())
+                .attached_deposit(MINT_STORAGE_COST)
+                .predecessor_account_id(accounts(0))
+                .build());
+            contract.nft_mint(token_id.to_string(), accounts(1), sample_token_metadata());
+        }
+        // a billion shares with 24 decimals, bought out for a hundred NEAR
+        let total_shares = 10u128.pow(33);
+        let reserve_price = 10u128.pow(26);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.fractionalize(
+            "0".to_string(),
+            U128(total_shares),
+            U128(reserve_price),
+            share_metadata(),
+        );
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(reserve_price + VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(3))
+            .build());
+        contract.buyout();
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        assert_eq!(contract.redeem_shares(), U128(reserve_price));
+        contract.fractionalize(
+            "1".to_string(),
+            U128(100),
+            U128(RESERVE_PRICE),
+            share_metadata(),
+        );
+        assert_eq!(contract.get_vault().unwrap().token_id, "1");
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn test_buyout_below_reserve_price() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RESERVE_PRICE - 1)
+            .predecessor_account_id(accounts(3))
+            .build());
+        contract.buyout();
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver is not on the allowlist")]
+    fn test_buyout_by_unlisted_buyer() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_transfer_policy(TransferPolicy {
+            receiver_list: ReceiverList::Allow,
+            min_holding_period: U64(0),
+        });
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RESERVE_PRICE)
+            .predecessor_account_id(accounts(3))
+            .build());
+        contract.buyout();
+    }
+
+    #[test]
+    #[should_panic(expected = "Account charlie is blocked")]
+    fn test_blocked_share_receiver() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(0))
+            .build());
+        contract.add_to_blocklist(accounts(2));
+        contract.storage_deposit(Some(accounts(2)), None);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.ft_transfer(accounts(2), U128(10), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sender not approved")]
+    fn test_vaulted_token_cannot_be_transferred() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.attached_deposit(1).build());
+        contract.nft_transfer(accounts(2), "0".to_string(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token has expired")]
+    fn test_buyout_of_expired_token() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+        let mut ticket = sample_token_metadata();
+        ticket.expires_at = Some("1000".to_string());
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint("ticket".to_string(), accounts(1), ticket);
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(1))
+            .build());
+        contract.fractionalize(
+            "ticket".to_string(),
+            U128(100),
+            U128(RESERVE_PRICE),
+            share_metadata(),
+        );
+
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(RESERVE_PRICE + VAULT_DEPOSIT)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(1_000_000_000)
+            .build());
+        contract.buyout();
+    }
+}
@@ -88,16 +88,13 @@ export default function App() {
 
 
           // pub fn nft_mint_default(
-          //   token_id: TokenId,
           //   receiver_id: AccountId,
+          // the token ID is drawn at random by the contract
 
           try {
 
-            var currentTimeInMilliseconds=Date.now();
-
             // make an update call to the smart contract
             await window.contract.nft_mint_default({
-              token_id: "" + currentTimeInMilliseconds,
               // pass the value that the user entered in the greeting field
               receiver_id: window.accountId
             },  